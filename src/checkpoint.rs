@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use eyre::{ensure, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::Endpoint;
+
+/// How far an upload got, saved next to the input after every acknowledged
+/// batch so that an interrupted upload can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The CSV file being uploaded.
+    pub input: PathBuf,
+    /// The SHA-256 of the input, as lowercase hex.
+    pub sha256: String,
+    /// The endpoint the input is uploaded to.
+    pub endpoint: String,
+    /// The last CSV line whose row was uploaded or rejected.
+    pub line: u64,
+}
+
+impl Checkpoint {
+    /// A checkpoint of an upload that has not acknowledged any line yet.
+    pub fn new(input: &Path, sha256: String, endpoint: Endpoint) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            sha256,
+            endpoint: endpoint.to_string(),
+            line: 0,
+        }
+    }
+
+    /// `accounts.csv` is checkpointed into `accounts.checkpoint.json`.
+    pub fn path_for(input: &Path) -> PathBuf {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        input.with_file_name(format!("{stem}.checkpoint.json"))
+    }
+
+    /// Reads the checkpoint at `path`, if there is one.
+    pub fn load(path: &Path) -> eyre::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read checkpoint {}", path.display()))?;
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .context(format!("Invalid checkpoint {}", path.display()))
+    }
+
+    /// Writes the checkpoint to a temporary file first, so that a crash
+    /// never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    /// Fails unless this checkpoint was written for the same input contents
+    /// and endpoint as `other`.
+    pub fn ensure_resumable(&self, other: &Checkpoint) -> eyre::Result<()> {
+        ensure!(
+            self.sha256 == other.sha256,
+            "{} changed since the checkpoint was written, refusing to resume",
+            other.input.display()
+        );
+        ensure!(
+            self.endpoint == other.endpoint,
+            "Checkpoint was written for endpoint {}, not {}",
+            self.endpoint,
+            other.endpoint
+        );
+
+        Ok(())
+    }
+}
+
+/// The hex SHA-256 digest of the file at `path`.
+pub fn sha256(path: &Path) -> eyre::Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buffer = vec![0; 1 << 16];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_file_contents() {
+        let path = std::env::temp_dir().join("feedzai-client-hash.csv");
+        std::fs::write(&path, "abc").unwrap();
+
+        let hash = sha256(&path).expect("Hashed file");
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn save_and_load_checkpoint() {
+        let path = std::env::temp_dir().join("feedzai-client.checkpoint.json");
+        let mut checkpoint = Checkpoint::new(
+            Path::new("accounts.csv"),
+            "abc".into(),
+            Endpoint::ReferenceDataAccount,
+        );
+        checkpoint.line = 42;
+
+        checkpoint.save(&path).expect("Saved checkpoint");
+        let loaded = Checkpoint::load(&path).expect("Loaded checkpoint");
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Some(checkpoint));
+    }
+
+    #[test]
+    fn refuse_to_resume_changed_input() {
+        let saved = Checkpoint::new(
+            Path::new("accounts.csv"),
+            "abc".into(),
+            Endpoint::ReferenceDataAccount,
+        );
+        let current = Checkpoint::new(
+            Path::new("accounts.csv"),
+            "def".into(),
+            Endpoint::ReferenceDataAccount,
+        );
+
+        assert!(saved.ensure_resumable(&current).is_err());
+        assert!(saved.ensure_resumable(&saved.clone()).is_ok());
+    }
+}
@@ -0,0 +1,262 @@
+/// Active ISO 4217 currency codes with the number of decimals of their
+/// minor unit, sorted by code.
+const CURRENCIES: &[(&str, u32)] = &[
+    ("AED", 2),
+    ("AFN", 2),
+    ("ALL", 2),
+    ("AMD", 2),
+    ("AOA", 2),
+    ("ARS", 2),
+    ("AUD", 2),
+    ("AWG", 2),
+    ("AZN", 2),
+    ("BAM", 2),
+    ("BBD", 2),
+    ("BDT", 2),
+    ("BGN", 2),
+    ("BHD", 3),
+    ("BIF", 0),
+    ("BMD", 2),
+    ("BND", 2),
+    ("BOB", 2),
+    ("BOV", 2),
+    ("BRL", 2),
+    ("BSD", 2),
+    ("BTN", 2),
+    ("BWP", 2),
+    ("BYN", 2),
+    ("BZD", 2),
+    ("CAD", 2),
+    ("CDF", 2),
+    ("CHE", 2),
+    ("CHF", 2),
+    ("CHW", 2),
+    ("CLF", 4),
+    ("CLP", 0),
+    ("CNY", 2),
+    ("COP", 2),
+    ("COU", 2),
+    ("CRC", 2),
+    ("CUP", 2),
+    ("CVE", 2),
+    ("CZK", 2),
+    ("DJF", 0),
+    ("DKK", 2),
+    ("DOP", 2),
+    ("DZD", 2),
+    ("EGP", 2),
+    ("ERN", 2),
+    ("ETB", 2),
+    ("EUR", 2),
+    ("FJD", 2),
+    ("FKP", 2),
+    ("GBP", 2),
+    ("GEL", 2),
+    ("GHS", 2),
+    ("GIP", 2),
+    ("GMD", 2),
+    ("GNF", 0),
+    ("GTQ", 2),
+    ("GYD", 2),
+    ("HKD", 2),
+    ("HNL", 2),
+    ("HTG", 2),
+    ("HUF", 2),
+    ("IDR", 2),
+    ("ILS", 2),
+    ("INR", 2),
+    ("IQD", 3),
+    ("IRR", 2),
+    ("ISK", 0),
+    ("JMD", 2),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KES", 2),
+    ("KGS", 2),
+    ("KHR", 2),
+    ("KMF", 0),
+    ("KPW", 2),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("KYD", 2),
+    ("KZT", 2),
+    ("LAK", 2),
+    ("LBP", 2),
+    ("LKR", 2),
+    ("LRD", 2),
+    ("LSL", 2),
+    ("LYD", 3),
+    ("MAD", 2),
+    ("MDL", 2),
+    ("MGA", 2),
+    ("MKD", 2),
+    ("MMK", 2),
+    ("MNT", 2),
+    ("MOP", 2),
+    ("MRU", 2),
+    ("MUR", 2),
+    ("MVR", 2),
+    ("MWK", 2),
+    ("MXN", 2),
+    ("MXV", 2),
+    ("MYR", 2),
+    ("MZN", 2),
+    ("NAD", 2),
+    ("NGN", 2),
+    ("NIO", 2),
+    ("NOK", 2),
+    ("NPR", 2),
+    ("NZD", 2),
+    ("OMR", 3),
+    ("PAB", 2),
+    ("PEN", 2),
+    ("PGK", 2),
+    ("PHP", 2),
+    ("PKR", 2),
+    ("PLN", 2),
+    ("PYG", 0),
+    ("QAR", 2),
+    ("RON", 2),
+    ("RSD", 2),
+    ("RUB", 2),
+    ("RWF", 0),
+    ("SAR", 2),
+    ("SBD", 2),
+    ("SCR", 2),
+    ("SDG", 2),
+    ("SEK", 2),
+    ("SGD", 2),
+    ("SHP", 2),
+    ("SLE", 2),
+    ("SOS", 2),
+    ("SRD", 2),
+    ("SSP", 2),
+    ("STN", 2),
+    ("SVC", 2),
+    ("SYP", 2),
+    ("SZL", 2),
+    ("THB", 2),
+    ("TJS", 2),
+    ("TMT", 2),
+    ("TND", 3),
+    ("TOP", 2),
+    ("TRY", 2),
+    ("TTD", 2),
+    ("TWD", 2),
+    ("TZS", 2),
+    ("UAH", 2),
+    ("UGX", 0),
+    ("USD", 2),
+    ("USN", 2),
+    ("UYI", 0),
+    ("UYU", 2),
+    ("UYW", 4),
+    ("UZS", 2),
+    ("VED", 2),
+    ("VES", 2),
+    ("VND", 0),
+    ("VUV", 0),
+    ("WST", 2),
+    ("XAF", 0),
+    ("XCD", 2),
+    ("XCG", 2),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("YER", 2),
+    ("ZAR", 2),
+    ("ZMW", 2),
+    ("ZWG", 2),
+];
+
+/// How many decimals amounts in `code` have, e.g. 2 for `EUR` and 0 for
+/// `JPY`, or `None` if `code` is not an ISO 4217 currency.
+pub fn exponent(code: &str) -> Option<u32> {
+    CURRENCIES
+        .binary_search_by(|(candidate, _)| (*candidate).cmp(code))
+        .ok()
+        .map(|index| CURRENCIES[index].1)
+}
+
+/// Parses the decimal `amount` exactly into minor units of a currency with
+/// `exponent` decimals, e.g. `"10.10"` into 1010 cents.
+///
+/// Returns `None` for anything but plain decimals, including `NaN`, `inf`
+/// and exponents, and for amounts with more significant decimals than the
+/// currency has.
+pub fn minor_units(amount: &str, exponent: u32) -> Option<i64> {
+    let (negative, whole, fraction) = split_decimal(amount)?;
+    let exponent = exponent as usize;
+    let (kept, rest) = fraction.split_at(fraction.len().min(exponent));
+
+    if rest.bytes().any(|b| b != b'0') {
+        return None;
+    }
+
+    let units = format!("{whole}{kept:0<exponent$}").parse::<i64>().ok()?;
+
+    Some(if negative { -units } else { units })
+}
+
+/// Whether `amount` is a plain decimal, like `-10.10`.
+pub fn is_decimal(amount: &str) -> bool {
+    split_decimal(amount).is_some()
+}
+
+/// The sign, whole and fractional digits of a plain decimal.
+fn split_decimal(amount: &str) -> Option<(bool, &str, &str)> {
+    let (negative, digits) = match amount.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, amount.strip_prefix('+').unwrap_or(amount)),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    Some((negative, whole, fraction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_up_exponents() {
+        assert_eq!(exponent("EUR"), Some(2));
+        assert_eq!(exponent("JPY"), Some(0));
+        assert_eq!(exponent("BHD"), Some(3));
+        assert_eq!(exponent("CLF"), Some(4));
+        assert_eq!(exponent("XYZ"), None);
+        assert_eq!(exponent("eur"), None);
+    }
+
+    #[test]
+    fn convert_to_minor_units() {
+        assert_eq!(minor_units("10.10", 2), Some(1010));
+        assert_eq!(minor_units("10.1", 2), Some(1010));
+        assert_eq!(minor_units("10", 2), Some(1000));
+        assert_eq!(minor_units(".5", 2), Some(50));
+        assert_eq!(minor_units("-0.01", 2), Some(-1));
+        assert_eq!(minor_units("1500", 0), Some(1500));
+        assert_eq!(minor_units("1500.00", 0), Some(1500));
+        assert_eq!(minor_units("1.234", 3), Some(1234));
+    }
+
+    #[test]
+    fn reject_inexact_amounts() {
+        for amount in [
+            "10.105", "NaN", "inf", "-inf", "1e3", "", ".", "-", "1,000.00",
+        ] {
+            assert_eq!(minor_units(amount, 2), None, "{amount}");
+        }
+
+        assert_eq!(minor_units("1500.5", 0), None);
+        assert_eq!(minor_units("99999999999999999999", 2), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use csv::StringRecord;
+use eyre::Context;
+use log::info;
+
+/// Columns appended to every rejected record.
+const COLUMNS: [&str; 3] = ["error_reason", "error_field", "line_number"];
+
+/// Writes rejected CSV records, with the reason they were rejected, to a
+/// file next to the input that can be fed back through the same endpoint.
+///
+/// The file is only created once the first record is rejected. Malformed
+/// records with more fields than the header are written as they were read.
+pub struct DeadLetter {
+    path: PathBuf,
+    headers: StringRecord,
+    writer: Option<csv::Writer<File>>,
+    append: bool,
+    count: usize,
+}
+
+impl DeadLetter {
+    /// Rejects records with `headers` to a new file next to `input`, removing
+    /// the reject file of a previous run right away so that a run without
+    /// rejects does not leave it behind.
+    pub fn new(input: &Path, headers: &StringRecord) -> eyre::Result<Self> {
+        let path = Self::path_for(input);
+
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .context(format!("Failed to remove reject file {}", path.display()))?;
+        }
+
+        Ok(Self {
+            path,
+            headers: headers.clone(),
+            writer: None,
+            append: false,
+            count: 0,
+        })
+    }
+
+    /// Like [`DeadLetter::new`], but appends to an existing reject file of a
+    /// resumed upload instead of replacing it.
+    pub fn appending(input: &Path, headers: &StringRecord) -> Self {
+        Self {
+            path: Self::path_for(input),
+            headers: headers.clone(),
+            writer: None,
+            append: true,
+            count: 0,
+        }
+    }
+
+    /// `accounts.csv` is rejected into `accounts.rejects.csv`.
+    pub fn path_for(input: &Path) -> PathBuf {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        input.with_file_name(format!("{stem}.rejects.csv"))
+    }
+
+    /// Indices of the input columns that are not dead-letter columns, so
+    /// that a reject file can be re-run without sending them to Feedzai.
+    pub fn input_columns(headers: &StringRecord) -> Vec<usize> {
+        headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| !COLUMNS.contains(header))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Writes `record`, read at `line`, with why and on which field it was
+    /// rejected.
+    pub fn reject(
+        &mut self,
+        record: &StringRecord,
+        line: u64,
+        reason: &str,
+        field: &str,
+    ) -> eyre::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None if self.append && self.path.exists() => {
+                let file = OpenOptions::new().append(true).open(&self.path)?;
+                let writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .flexible(true)
+                    .from_writer(file);
+                self.writer.insert(writer)
+            }
+            None => {
+                let mut writer = csv::WriterBuilder::new()
+                    .flexible(true)
+                    .from_path(&self.path)?;
+                writer.write_record(self.headers.iter().chain(COLUMNS))?;
+                self.writer.insert(writer)
+            }
+        };
+
+        let line = line.to_string();
+        writer.write_record(record.iter().chain([reason, field, line.as_str()]))?;
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Writes the buffered records to the file.
+    pub fn flush(&mut self) -> eyre::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the file and logs how many records were rejected.
+    pub fn finish(self) -> eyre::Result<()> {
+        if let Some(mut writer) = self.writer {
+            writer.flush()?;
+            info!(
+                "{} rejected rows written to {}",
+                self.count,
+                self.path.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_rejected_records_with_reason() {
+        let dir = std::env::temp_dir().join("feedzai-client-dead-letter");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+
+        let headers = StringRecord::from(vec!["account_id", "account_open_date"]);
+        let mut dead_letter = DeadLetter::new(&input, &headers).expect("Dead letter");
+
+        dead_letter
+            .reject(
+                &StringRecord::from(vec!["a1", "yesterday"]),
+                3,
+                "column account_open_date is \"yesterday\", expected int",
+                "account_open_date",
+            )
+            .expect("Rejected record");
+        dead_letter.finish().expect("Flushed reject file");
+
+        let content = std::fs::read_to_string(dir.join("accounts.rejects.csv")).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            content,
+            "account_id,account_open_date,error_reason,error_field,line_number\n\
+             a1,yesterday,\"column account_open_date is \"\"yesterday\"\", expected int\",account_open_date,3\n"
+        );
+    }
+
+    #[test]
+    fn append_to_reject_file_of_resumed_upload() {
+        let dir = std::env::temp_dir().join("feedzai-client-dead-letter-append");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+        let headers = StringRecord::from(vec!["account_id"]);
+
+        for (line, id) in [(2, "a1"), (5, "a4")] {
+            let mut dead_letter = DeadLetter::appending(&input, &headers);
+            dead_letter
+                .reject(&StringRecord::from(vec![id]), line, "HTTP 400", "")
+                .expect("Rejected record");
+            dead_letter.finish().expect("Flushed reject file");
+        }
+
+        let content = std::fs::read_to_string(dir.join("accounts.rejects.csv")).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            content,
+            "account_id,error_reason,error_field,line_number\n\
+             a1,HTTP 400,,2\n\
+             a4,HTTP 400,,5\n"
+        );
+    }
+
+    #[test]
+    fn remove_reject_file_of_previous_run() {
+        let dir = std::env::temp_dir().join("feedzai-client-dead-letter-stale");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+        let path = DeadLetter::path_for(&input);
+
+        std::fs::write(&path, "account_id,error_reason,error_field,line_number\n").unwrap();
+
+        let dead_letter =
+            DeadLetter::new(&input, &StringRecord::from(vec!["account_id"])).expect("Dead letter");
+        dead_letter.finish().expect("Flushed reject file");
+
+        let exists = path.exists();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!exists);
+    }
+
+    #[test]
+    fn skip_dead_letter_columns_of_reject_file() {
+        let headers = StringRecord::from(vec![
+            "account_id",
+            "error_reason",
+            "account_active",
+            "error_field",
+            "line_number",
+        ]);
+
+        assert_eq!(DeadLetter::input_columns(&headers), vec![0, 2]);
+    }
+}
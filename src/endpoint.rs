@@ -0,0 +1,84 @@
+use crate::schema::Schema;
+
+/// A Feedzai ingestion endpoint, named on the command line, in logs and in
+/// reports as e.g. `ref_account`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, strum::Display)]
+pub enum Endpoint {
+    /// Account reference data.
+    #[clap(name = "ref_account")]
+    #[strum(serialize = "ref_account")]
+    ReferenceDataAccount,
+    /// Card reference data.
+    #[clap(name = "ref_card")]
+    #[strum(serialize = "ref_card")]
+    ReferenceDataCard,
+    /// Customer reference data.
+    #[clap(name = "ref_customer")]
+    #[strum(serialize = "ref_customer")]
+    ReferenceDataCustomer,
+    /// Device reference data.
+    #[clap(name = "ref_device")]
+    #[strum(serialize = "ref_device")]
+    ReferenceDataDevice,
+    /// Card authorization events.
+    #[clap(name = "card_auth")]
+    #[strum(serialize = "card_auth")]
+    CardAuthorization,
+    /// Card clearing events.
+    #[clap(name = "card_clear")]
+    #[strum(serialize = "card_clear")]
+    CardClearing,
+    /// Transfer initiation events.
+    #[clap(name = "transfer_init")]
+    #[strum(serialize = "transfer_init")]
+    TransferInitiation,
+    /// Transfer settlement events.
+    #[clap(name = "transfer_settle")]
+    #[strum(serialize = "transfer_settle")]
+    TransferSettlement,
+}
+
+impl Endpoint {
+    /// The REST path events for this endpoint are posted to.
+    pub fn path(self) -> &'static str {
+        match self {
+            Endpoint::ReferenceDataAccount => "/api/v1/reference-data/accounts",
+            Endpoint::ReferenceDataCard => "/api/v1/reference-data/cards",
+            Endpoint::ReferenceDataCustomer => "/api/v1/reference-data/customers",
+            Endpoint::ReferenceDataDevice => "/api/v1/reference-data/devices",
+            Endpoint::CardAuthorization => "/api/v1/events/card-authorizations",
+            Endpoint::CardClearing => "/api/v1/events/card-clearings",
+            Endpoint::TransferInitiation => "/api/v1/events/transfer-initiations",
+            Endpoint::TransferSettlement => "/api/v1/events/transfer-settlements",
+        }
+    }
+
+    /// The field identifying an event of this endpoint.
+    pub fn id_field(self) -> &'static str {
+        match self {
+            Endpoint::ReferenceDataAccount => "account_id",
+            Endpoint::ReferenceDataCard => "card_id",
+            Endpoint::ReferenceDataCustomer => "customer_id",
+            Endpoint::ReferenceDataDevice => "device_id",
+            Endpoint::CardAuthorization | Endpoint::CardClearing => "transaction_id",
+            Endpoint::TransferInitiation | Endpoint::TransferSettlement => "transfer_id",
+        }
+    }
+
+    /// The REST path batches of events for this endpoint are posted to, if
+    /// Feedzai supports bulk ingestion for it.
+    pub fn bulk_path(self) -> Option<&'static str> {
+        match self {
+            Endpoint::ReferenceDataAccount => Some("/api/v1/reference-data/accounts/bulk"),
+            Endpoint::ReferenceDataCard => Some("/api/v1/reference-data/cards/bulk"),
+            Endpoint::ReferenceDataCustomer => Some("/api/v1/reference-data/customers/bulk"),
+            Endpoint::ReferenceDataDevice => Some("/api/v1/reference-data/devices/bulk"),
+            _ => None,
+        }
+    }
+
+    /// The built-in schema for this endpoint.
+    pub fn schema(self) -> Schema {
+        Schema::builtin(self)
+    }
+}
@@ -0,0 +1,163 @@
+use thiserror::Error;
+
+/// The JSON type a CSV cell was expected to convert to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum FieldType {
+    /// A JSON array.
+    Array,
+    /// A 64-bit integer.
+    Int,
+    /// A 64-bit floating point number.
+    Float,
+    /// A string.
+    Str,
+    /// `true` or `false`.
+    Bool,
+    /// Epoch millis, from one of the timestamp formats of a schema.
+    Timestamp,
+    /// A decimal amount, converted to minor units of its currency.
+    Amount,
+    /// A JSON object.
+    Object,
+}
+
+/// A problem with a single field of an event.
+#[derive(Debug, Clone, PartialEq, Error, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum FieldError {
+    /// A value did not convert to the expected type.
+    #[error("column {column} is {value:?}, expected {expected}")]
+    InvalidValue {
+        /// The column of the value.
+        column: String,
+        /// The CSV text of the value.
+        value: String,
+        /// The type the value should have converted to.
+        expected: FieldType,
+    },
+
+    /// A timestamp is before 1970 or in the future.
+    #[error("column {column} is {value:?}, which is before 1970 or in the future")]
+    OutOfRange {
+        /// The column of the timestamp.
+        column: String,
+        /// The CSV text of the timestamp.
+        value: String,
+    },
+
+    /// A currency column does not hold an ISO 4217 currency code.
+    #[error("column {column} is {value:?}, which is not an ISO 4217 currency")]
+    UnknownCurrency {
+        /// The currency column.
+        column: String,
+        /// The CSV text of the currency.
+        value: String,
+    },
+
+    /// An amount has more decimals than the minor unit of its currency.
+    #[error("column {column} is {value:?}, which has more decimals than {currency} allows")]
+    TooPrecise {
+        /// The column of the amount.
+        column: String,
+        /// The CSV text of the amount.
+        value: String,
+        /// The currency of the amount.
+        currency: String,
+    },
+
+    /// A column is empty and its schema rejects empty cells.
+    #[error("column {column} is empty")]
+    Empty {
+        /// The empty column.
+        column: String,
+    },
+
+    /// A required column is missing.
+    #[error("column {column} is required")]
+    Missing {
+        /// The missing column.
+        column: String,
+    },
+
+    /// A column is not in the schema of a strict validator.
+    #[error("column {column} is unknown")]
+    Unknown {
+        /// The unknown column.
+        column: String,
+    },
+
+    /// A value is not one of the values allowed for its column.
+    #[error("column {column} is {value:?}, expected one of {}", allowed.join(", "))]
+    NotAllowed {
+        /// The column of the value.
+        column: String,
+        /// The CSV text of the value.
+        value: String,
+        /// The values allowed for the column.
+        allowed: Vec<String>,
+    },
+
+    /// The event is not a JSON object, so it has no fields.
+    #[error("event is not an object")]
+    NotAnObject,
+
+    /// A CSV record could not be read as a row, e.g. because it has more or
+    /// fewer fields than the header or is not valid UTF-8.
+    #[error("record is malformed: {reason}")]
+    Malformed {
+        /// What is wrong with the record.
+        reason: String,
+    },
+}
+
+impl FieldError {
+    /// The column the error refers to, if any.
+    pub fn column(&self) -> Option<&str> {
+        match self {
+            FieldError::InvalidValue { column, .. }
+            | FieldError::OutOfRange { column, .. }
+            | FieldError::UnknownCurrency { column, .. }
+            | FieldError::TooPrecise { column, .. }
+            | FieldError::Empty { column }
+            | FieldError::Missing { column }
+            | FieldError::Unknown { column }
+            | FieldError::NotAllowed { column, .. } => Some(column),
+            FieldError::NotAnObject | FieldError::Malformed { .. } => None,
+        }
+    }
+
+    /// The error of a field of an object nested in `parent`, e.g. column
+    /// `limit_type` in `account_limits[0]` becomes
+    /// `account_limits[0].limit_type`.
+    pub fn nested_in(mut self, parent: &str) -> Self {
+        match &mut self {
+            FieldError::InvalidValue { column, .. }
+            | FieldError::OutOfRange { column, .. }
+            | FieldError::UnknownCurrency { column, .. }
+            | FieldError::TooPrecise { column, .. }
+            | FieldError::Empty { column }
+            | FieldError::Missing { column }
+            | FieldError::Unknown { column }
+            | FieldError::NotAllowed { column, .. } => *column = format!("{parent}.{column}"),
+            FieldError::NotAnObject | FieldError::Malformed { .. } => {}
+        }
+
+        self
+    }
+
+    /// A short name for the kind of error, e.g. `invalid_value`.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// A [`FieldError`] located at a line of the input CSV.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("line {line}: {error}")]
+pub struct RowError {
+    /// The line of the row in the input CSV, starting at 1 for the header.
+    pub line: u64,
+    /// What is wrong with the row.
+    pub error: FieldError,
+}
@@ -0,0 +1,60 @@
+#![warn(missing_docs)]
+
+//! Validates CSV exports against Feedzai's schemas and uploads them.
+//!
+//! An [`Event`] is built from a CSV row with one string field per column,
+//! turned into the payload Feedzai expects by a [`Validator`] such as the
+//! built-in [`Schema`](schema::Schema) of an [`Endpoint`], and sent with an
+//! [`Uploader`](upload::Uploader). Events can also be built in Rust from the
+//! typed [`model`]s.
+//!
+//! ```no_run
+//! use feedzai_client::{upload::Uploader, Endpoint, EventValidation};
+//! use serde_json::json;
+//!
+//! let event = json!({"account_id": "a1", "account_number_of_cards": "2"})
+//!     .validate(&Endpoint::ReferenceDataAccount.schema())
+//!     .expect("Valid event");
+//!
+//! let status = Uploader::new("https://feedzai.example.com")
+//!     .upload(Endpoint::ReferenceDataAccount, &event)?;
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+/// Credentials and TLS settings.
+pub mod auth;
+/// Resuming interrupted uploads.
+pub mod checkpoint;
+/// ISO 4217 currencies and exact amounts.
+pub mod currency;
+/// Writing rejected rows back to CSV.
+pub mod dead_letter;
+mod endpoint;
+/// Validation errors.
+pub mod error;
+/// Typed events per endpoint.
+pub mod model;
+/// Dotted paths to the fields of nested objects.
+pub mod path;
+/// Validating and uploading a CSV file from start to end.
+pub mod pipeline;
+/// A progress bar over CSV rows.
+pub mod progress;
+mod rate_limit;
+/// The machine-readable report of a run.
+pub mod report;
+/// Declarative validators.
+pub mod schema;
+/// Validation errors grouped for logging.
+pub mod summary;
+/// Timestamp formats and time zones.
+pub mod timestamp;
+/// Sending events to Feedzai.
+pub mod upload;
+mod validation;
+
+pub use endpoint::Endpoint;
+pub use validation::{EventValidation, Validator};
+
+/// A Feedzai event, as a JSON object.
+pub type Event = serde_json::Value;
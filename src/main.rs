@@ -1,41 +1,57 @@
-use std::{ffi::OsStr, path::PathBuf};
-
-use clap::Parser;
-use eyre::{ensure, Context};
-use itertools::Itertools;
-use log::{debug, info};
-
-type Event = serde_json::Value;
-
-fn main() -> eyre::Result<()> {
+use std::{
+    ffi::OsStr,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Parser, ValueEnum};
+use eyre::ensure;
+use log::{info, LevelFilter};
+use simple_logger::SimpleLogger;
+
+use feedzai_client::{
+    auth::{tls_config, Auth, Secret},
+    pipeline::{Config, Pipeline},
+    schema::Schema,
+    upload::{RetryPolicy, Uploader},
+    Endpoint,
+};
+
+/// The exit code of a run that completed but rejected some rows.
+const PARTIAL_FAILURE: u8 = 3;
+
+fn main() -> eyre::Result<ExitCode> {
     let args = Args::parse();
 
-    simple_logger::init_with_level(args.log_level()).unwrap();
+    args.logger().init().unwrap();
 
     info!("input: {}", args.input.display());
     info!("endpoint: {}", args.endpoint);
+    if let Some(url) = &args.url {
+        info!("url: {url}");
+    }
 
-    let mut reader = csv::Reader::from_path(args.input)?;
-    let headers = reader.headers()?.clone();
+    let (report, error) = match Pipeline::run(&args.config()?) {
+        Ok(report) => (report, None),
+        Err(aborted) => (aborted.report, Some(aborted.error)),
+    };
 
-    debug!("headers: {headers:?}");
+    if let Some(path) = &args.report {
+        report.write(path)?;
+        info!("report written to {}", path.display());
+    }
 
-    let events = reader
-        .records()
-        .map_ok(|record| {
-            headers
-                .iter()
-                .map(ToString::to_string)
-                .zip(record.iter().map(ToString::to_string))
-                .collect::<Event>()
-                .validate(args.endpoint.validator())
-        })
-        .flatten_ok()
-        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(error) = error {
+        return Err(error);
+    }
 
-    debug!("events: {events:?}");
+    if report.is_partial_failure() {
+        return Ok(ExitCode::from(PARTIAL_FAILURE));
+    }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
 #[derive(Debug, Parser)]
@@ -49,224 +65,320 @@ struct Args {
     #[arg(value_enum)]
     endpoint: Endpoint,
 
-    /// Whether to print debug information.
-    #[clap(short, long)]
-    #[arg(default_value_t = false)]
-    debug: bool,
-}
-
-impl Args {
-    fn log_level(&self) -> log::Level {
-        if self.debug {
-            log::Level::Debug
-        } else {
-            log::Level::Info
-        }
-    }
-}
-
-fn csv_file(value: &str) -> eyre::Result<PathBuf> {
-    let path = PathBuf::from(value);
-    ensure!(path.is_file(), "not a file");
-    ensure!(
-        path.extension() == Some(OsStr::new("csv")),
-        "not a CSV file"
-    );
-    Ok(path)
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, strum::Display)]
-enum Endpoint {
-    #[clap(name = "ref_account")]
-    ReferenceDataAccount,
-    #[clap(name = "ref_card")]
-    ReferenceDataCard,
-    #[clap(name = "ref_customer")]
-    ReferenceDataCustomer,
-    #[clap(name = "ref_device")]
-    ReferenceDataDevice,
-    #[clap(name = "card_auth")]
-    CardAuthorization,
-    #[clap(name = "card_clear")]
-    CardClearing,
-    #[clap(name = "transfer_init")]
-    TransferInitiation,
-    #[clap(name = "transfer_settle")]
-    TransferSettlement,
-}
+    /// The base URL of the Feedzai instance.
+    #[clap(short, long, env = "FEEDZAI_URL")]
+    #[arg(required_unless_present = "dry_run")]
+    url: Option<String>,
 
-impl Endpoint {
-    fn validator(self) -> impl Validator {
-        match self {
-            Endpoint::ReferenceDataAccount => ReferenceDataAccountValidator,
-            _ => todo!(),
-        }
-    }
-}
-
-trait Validator {
-    fn validate(&self, event: Event) -> eyre::Result<Event>;
-}
+    /// How many events to send per request to endpoints supporting bulk
+    /// ingestion.
+    #[clap(short, long)]
+    #[arg(default_value_t = NonZeroUsize::MIN)]
+    batch_size: NonZeroUsize,
 
-trait EventValidation {
-    fn validate(self, validator: impl Validator) -> eyre::Result<Event>;
+    /// How many threads validate rows and upload batches in parallel.
+    #[clap(short, long)]
+    #[arg(default_value_t = NonZeroUsize::MIN)]
+    concurrency: NonZeroUsize,
+
+    /// The API key to authenticate to Feedzai with.
+    #[clap(long, env = "FEEDZAI_API_KEY", hide_env_values = true)]
+    api_key: Option<Secret>,
+
+    /// A file containing a bearer token to authenticate to Feedzai with.
+    #[clap(long, env = "FEEDZAI_BEARER_TOKEN_FILE")]
+    #[arg(conflicts_with = "api_key")]
+    bearer_token_file: Option<PathBuf>,
+
+    /// A PEM client certificate chain for mutual TLS.
+    #[clap(long, env = "FEEDZAI_CLIENT_CERT")]
+    #[arg(requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// The PEM private key of the client certificate.
+    #[clap(long, env = "FEEDZAI_CLIENT_KEY")]
+    #[arg(requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// A PEM bundle of CA certificates to trust instead of the default ones.
+    #[clap(long, env = "FEEDZAI_CA_BUNDLE")]
+    ca_bundle: Option<PathBuf>,
+
+    /// How many times a request failing with 429, 5xx or a connection error
+    /// is sent at most.
+    #[clap(long)]
+    #[arg(default_value_t = NonZeroU32::new(5).unwrap())]
+    max_attempts: NonZeroU32,
+
+    /// The delay in milliseconds before the first retry, doubled before every
+    /// following one up to a minute, unless Feedzai sends a Retry-After. A
+    /// Retry-After longer than a minute is not waited for.
+    #[clap(long)]
+    #[arg(default_value_t = 500)]
+    retry_delay_ms: u64,
+
+    /// How many requests per second are sent to Feedzai at most, across all
+    /// threads.
+    #[clap(long, value_parser = positive_rps)]
+    max_rps: Option<f64>,
+
+    /// Overrides --max-rps for one endpoint, as `ENDPOINT=RPS`. May be given
+    /// once per endpoint.
+    #[clap(long, value_parser = endpoint_rps)]
+    endpoint_max_rps: Vec<(Endpoint, f64)>,
+
+    /// Whether to only validate the input and write the events that would
+    /// be uploaded as NDJSON, instead of uploading them.
+    #[clap(long)]
+    #[arg(default_value_t = false)]
+    dry_run: bool,
 
-    fn drop_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// Where to write the events of a dry run, stdout by default.
+    #[clap(short, long)]
+    #[arg(requires = "dry_run")]
+    output: Option<PathBuf>,
 
-    fn array_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// Where to write a JSON report of the run. Whether or not it is written,
+    /// the exit code is 3 if any row was rejected.
+    #[clap(long)]
+    report: Option<PathBuf>,
 
-    fn int_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// A TOML or JSON schema file overriding the endpoint's built-in one.
+    #[clap(short, long)]
+    schema: Option<PathBuf>,
 
-    fn float_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// Whether to skip the rows already uploaded by an interrupted run of the
+    /// same input and endpoint, according to its checkpoint file.
+    #[clap(long)]
+    #[arg(default_value_t = false, conflicts_with = "dry_run")]
+    resume: bool,
 
-    fn str_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// Whether to reject columns not mentioned by the schema, as if it
+    /// were strict.
+    #[clap(long)]
+    #[arg(default_value_t = false)]
+    strict: bool,
 
-    fn bool_fields(self, keys: &[&str]) -> eyre::Result<Self>
-    where
-        Self: Sized;
+    /// Whether to stop at the first invalid field instead of validating
+    /// the whole file.
+    #[clap(long)]
+    #[arg(default_value_t = false)]
+    fail_fast: bool,
 
-    fn convert(
-        &mut self,
-        key: &str,
-        f: fn(&str) -> eyre::Result<serde_json::Value>,
-    ) -> eyre::Result<&mut Event>;
+    /// Whether to print debug information.
+    #[clap(short, long)]
+    #[arg(default_value_t = false)]
+    debug: bool,
 }
 
-impl EventValidation for Event {
-    fn validate(self, validator: impl Validator) -> eyre::Result<Event> {
-        validator.validate(self)
+impl Args {
+    fn config(&self) -> eyre::Result<Config> {
+        let mut schema = match &self.schema {
+            Some(path) => Schema::from_path(path)?,
+            None => self.endpoint.schema(),
+        };
+
+        schema.strict |= self.strict;
+
+        Ok(Config {
+            input: self.input.clone(),
+            endpoint: self.endpoint,
+            schema,
+            uploader: if self.dry_run {
+                None
+            } else {
+                Some(self.uploader()?)
+            },
+            output: self.output.clone(),
+            batch_size: self.batch_size,
+            concurrency: self.concurrency,
+            resume: self.resume,
+            fail_fast: self.fail_fast,
+        })
     }
 
-    fn drop_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        assert!(self.is_object());
-
-        let obj = self.as_object_mut().unwrap();
+    fn uploader(&self) -> eyre::Result<Uploader> {
+        let url = self.url.as_deref().expect("--url is required by clap");
+        let mut uploader = Uploader::new(url).with_retry(RetryPolicy {
+            max_attempts: self.max_attempts.get(),
+            base_delay: Duration::from_millis(self.retry_delay_ms),
+            ..Default::default()
+        });
 
-        for &key in keys {
-            if obj.contains_key(key) {
-                obj.remove(key);
-            }
+        if let Some(auth) = self.auth()? {
+            uploader = uploader.with_auth(auth);
         }
 
-        Ok(self)
-    }
-
-    fn array_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        for &key in keys {
-            self.convert(key, |s| {
-                Ok(serde_json::Value::Array(serde_json::from_str(s)?))
-            })
-            .context(format!("Field {key} is not an array"))?;
+        if self.ca_bundle.is_some() || self.client_cert.is_some() {
+            uploader = uploader.with_tls(tls_config(
+                self.ca_bundle.as_deref(),
+                self.client_cert.as_deref().zip(self.client_key.as_deref()),
+            )?);
         }
 
-        Ok(self)
-    }
-
-    fn int_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        for &key in keys {
-            self.convert(key, |s| Ok(s.parse::<i64>()?.into()))?;
+        if let Some(max_rps) = self.max_rps() {
+            info!("max rps: {max_rps}");
+            uploader = uploader.with_max_rps(max_rps);
         }
 
-        Ok(self)
+        Ok(uploader)
     }
 
-    fn float_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        for &key in keys {
-            self.convert(key, |s| Ok(s.parse::<f64>()?.into()))?;
+    fn auth(&self) -> eyre::Result<Option<Auth>> {
+        if let Some(path) = &self.bearer_token_file {
+            return Auth::bearer_from_file(path).map(Some);
         }
 
-        Ok(self)
+        Ok(self.api_key.clone().map(Auth::ApiKey))
     }
 
-    fn str_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        for &key in keys {
-            self.convert(key, |s| Ok(s.into()))?;
-        }
-
-        Ok(self)
+    /// The request rate limit for the selected endpoint, if any.
+    fn max_rps(&self) -> Option<f64> {
+        self.endpoint_max_rps
+            .iter()
+            .rev()
+            .find(|(endpoint, _)| *endpoint == self.endpoint)
+            .map(|(_, max_rps)| *max_rps)
+            .or(self.max_rps)
     }
 
-    fn bool_fields(mut self, keys: &[&str]) -> eyre::Result<Self> {
-        for &key in keys {
-            self.convert(key, |s| Ok(s.parse::<bool>()?.into()))?;
-        }
+    /// Logs at debug level with --debug, except for ureq, whose debug logs
+    /// include request headers and so the API key.
+    fn logger(&self) -> SimpleLogger {
+        let level = if self.debug {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        };
 
-        Ok(self)
+        SimpleLogger::new()
+            .with_level(level)
+            .with_module_level("ureq", level.min(LevelFilter::Info))
     }
+}
 
-    fn convert(
-        &mut self,
-        key: &str,
-        f: fn(&str) -> eyre::Result<serde_json::Value>,
-    ) -> eyre::Result<&mut Event> {
-        assert!(self.is_object());
-
-        let obj = self.as_object_mut().unwrap();
-
-        if obj.contains_key(key) {
-            obj[key] = f(obj.get(key).unwrap().as_str().unwrap())?;
-        }
+fn positive_rps(value: &str) -> eyre::Result<f64> {
+    let rps = value.parse::<f64>()?;
+    ensure!(rps.is_finite() && rps > 0.0, "not a positive number");
+    Ok(rps)
+}
 
-        Ok(self)
-    }
+fn endpoint_rps(value: &str) -> eyre::Result<(Endpoint, f64)> {
+    let (endpoint, rps) = value
+        .split_once('=')
+        .ok_or_else(|| eyre::eyre!("expected ENDPOINT=RPS"))?;
+    let endpoint = Endpoint::from_str(endpoint, false).map_err(|err| eyre::eyre!(err))?;
+    Ok((endpoint, positive_rps(rps)?))
 }
 
-struct ReferenceDataAccountValidator;
-
-impl Validator for ReferenceDataAccountValidator {
-    fn validate(&self, event: Event) -> eyre::Result<Event> {
-        event
-            .drop_fields(&["key", "event_external_id"])?
-            .array_fields(&["account_cards", "account_customers", "account_limits"])?
-            .int_fields(&["account_number_of_cards", "account_open_date"])?
-            .str_fields(&["account_active"])
-
-        // return {
-        //     "drop": ["customer_id", "key", "event_external_id"],
-        //     "array": ["account_cards", "account_customers", "account_limits"],
-        //     "int": ["account_number_of_cards", "account_open_date"],
-        //     "str": "account_active",
-        // }
-    }
+fn csv_file(value: &str) -> eyre::Result<PathBuf> {
+    let path = PathBuf::from(value);
+    ensure!(path.is_file(), "not a file");
+    ensure!(
+        path.extension() == Some(OsStr::new("csv")),
+        "not a CSV file"
+    );
+    Ok(path)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        process::Command,
+    };
+
     use serde_json::json;
 
     use super::*;
 
     #[test]
-    fn validate_array_fields() {
-        let input = json!({
-            "field": r#"["one","two"]"#,
-            "expected": ["one","two"]
-        });
-
-        let event = input.array_fields(&["field"]).expect("Validated event");
-
-        assert_eq!(event["field"], event["expected"]);
+    fn override_max_rps_per_endpoint() {
+        let input = std::env::temp_dir().join("feedzai-client-args.csv");
+        std::fs::write(&input, "card_id\n").unwrap();
+
+        let args = Args::parse_from([
+            "feedzai-client",
+            "--input",
+            input.to_str().unwrap(),
+            "--endpoint",
+            "ref_card",
+            "--dry-run",
+            "--max-rps",
+            "10",
+            "--endpoint-max-rps",
+            "ref_account=2",
+            "--endpoint-max-rps",
+            "ref_card=5",
+        ]);
+
+        assert_eq!(args.max_rps(), Some(5.0));
     }
 
     #[test]
-    fn validate_int_fields() {
-        let input = json!({
-            "field": "123",
-            "expected": 123
+    fn never_log_api_key() {
+        // The logger is global, so it is set up in a child process running
+        // only this test.
+        const CHILD: &str = "FEEDZAI_CLIENT_TEST_LOGGING";
+
+        if std::env::var_os(CHILD).is_none() {
+            let output = Command::new(std::env::current_exe().unwrap())
+                .args(["tests::never_log_api_key", "--exact", "--nocapture"])
+                .env(CHILD, "1")
+                .output()
+                .expect("Ran child test");
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            assert!(output.status.success(), "{stderr}");
+            assert!(stderr.contains("DEBUG"), "{stderr}");
+            assert!(!stderr.contains("SUPERSECRET"), "{stderr}");
+            return;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let input = std::env::temp_dir().join("feedzai-client-logging.csv");
+        std::fs::write(&input, "account_id\n").unwrap();
+
+        let args = Args::parse_from([
+            "feedzai-client",
+            "--input",
+            input.to_str().unwrap(),
+            "--endpoint",
+            "ref_account",
+            "--url",
+            &url,
+            "--api-key",
+            "SUPERSECRET",
+            "--debug",
+        ]);
+
+        args.logger().init().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
         });
 
-        let event = input.int_fields(&["field"]).expect("Validated event");
+        let status = args
+            .uploader()
+            .unwrap()
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .unwrap();
 
-        assert_eq!(event["field"], event["expected"]);
+        server.join().unwrap();
+        log::debug!("uploaded with {status}");
     }
 }
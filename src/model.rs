@@ -0,0 +1,487 @@
+//! Typed Feedzai events, one per [`Endpoint`].
+//!
+//! They serialize to the same JSON as the events validated by the built-in
+//! schemas, and refuse fields Feedzai does not know.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Endpoint, Event};
+
+/// An event with a known shape, sent to a single endpoint.
+pub trait TypedEvent: Serialize + DeserializeOwned {
+    /// The endpoint this kind of event is sent to.
+    const ENDPOINT: Endpoint;
+
+    /// Reads a validated event, failing on missing ids, unknown fields and
+    /// fields of the wrong type.
+    fn from_event(event: Event) -> serde_json::Result<Self> {
+        serde_json::from_value(event)
+    }
+
+    /// The JSON sent to Feedzai for this event.
+    fn to_event(&self) -> Event {
+        serde_json::to_value(self).expect("Events serialize to JSON")
+    }
+}
+
+/// An account, as sent to the account reference data endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    /// The id of the account.
+    pub account_id: String,
+    /// The ids of the cards of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_cards: Option<Vec<String>>,
+    /// The ids of the customers owning the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_customers: Option<Vec<String>>,
+    /// The limits of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_limits: Option<Vec<AccountLimit>>,
+    /// How many cards the account has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_number_of_cards: Option<i64>,
+    /// When the account was opened, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_open_date: Option<i64>,
+    /// Whether the account is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_active: Option<String>,
+}
+
+impl TypedEvent for Account {
+    const ENDPOINT: Endpoint = Endpoint::ReferenceDataAccount;
+}
+
+/// A spending limit of an account.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountLimit {
+    /// What the limit applies to, e.g. `daily`.
+    pub limit_type: String,
+    /// The limit, in minor units of its currency.
+    pub limit_amount: i64,
+    /// The ISO 4217 code of the currency of the limit.
+    pub limit_currency: String,
+}
+
+/// A card, as sent to the card reference data endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Card {
+    /// The id of the card.
+    pub card_id: String,
+    /// The ids of the accounts the card draws on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_accounts: Option<Vec<String>>,
+    /// When the card was issued, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_issue_date: Option<i64>,
+    /// When the card expires, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_expiry_date: Option<i64>,
+    /// How much can be spent with the card per day, in minor units of the
+    /// card currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_daily_limit: Option<i64>,
+    /// The ISO 4217 code of the currency of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_currency: Option<String>,
+    /// The status of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_status: Option<String>,
+    /// The bank identification number of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_bin: Option<String>,
+    /// Whether the card only exists online.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_is_virtual: Option<bool>,
+    /// Whether the card supports contactless payments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_is_contactless: Option<bool>,
+}
+
+impl TypedEvent for Card {
+    const ENDPOINT: Endpoint = Endpoint::ReferenceDataCard;
+}
+
+/// A customer, as sent to the customer reference data endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Customer {
+    /// The id of the customer.
+    pub customer_id: String,
+    /// The ids of the accounts of the customer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_accounts: Option<Vec<String>>,
+    /// The addresses of the customer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_addresses: Option<Vec<Address>>,
+    /// When the customer was born, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_birth_date: Option<i64>,
+    /// When the customer registered, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_registration_date: Option<i64>,
+    /// The phone number of the customer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_phone_number: Option<String>,
+    /// The zip code of the customer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_zip_code: Option<String>,
+    /// Whether the customer is a politically exposed person.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_is_pep: Option<bool>,
+    /// Whether the identity of the customer was verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_is_kyc_verified: Option<bool>,
+}
+
+impl TypedEvent for Customer {
+    const ENDPOINT: Endpoint = Endpoint::ReferenceDataCustomer;
+}
+
+/// An address of a customer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Address {
+    /// What the address is used for, e.g. `home`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_type: Option<String>,
+    /// The street and house number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street: Option<String>,
+    /// The city.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    /// The postal code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    /// The country.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Whether this is the main address of the customer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_primary: Option<bool>,
+}
+
+/// A device, as sent to the device reference data endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Device {
+    /// The id of the device.
+    pub device_id: String,
+    /// The ids of the customers using the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_customers: Option<Vec<String>>,
+    /// When the device was first seen, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_first_seen: Option<i64>,
+    /// When the device was last seen, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_last_seen: Option<i64>,
+    /// The latitude the device was last seen at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_latitude: Option<f64>,
+    /// The longitude the device was last seen at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_longitude: Option<f64>,
+    /// Whether the device is rooted or jailbroken.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_is_rooted: Option<bool>,
+    /// Whether the device is an emulator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_is_emulator: Option<bool>,
+}
+
+impl TypedEvent for Device {
+    const ENDPOINT: Endpoint = Endpoint::ReferenceDataDevice;
+}
+
+/// A card authorization event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardAuthorization {
+    /// The id of the transaction.
+    pub transaction_id: String,
+    /// When the event happened, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_timestamp: Option<i64>,
+    /// When the transaction happened, in the merchant's local time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_local_time: Option<i64>,
+    /// The amount, in minor units of the transaction currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_amount: Option<i64>,
+    /// The ISO 4217 code of the transaction currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_currency: Option<String>,
+    /// The amount, in cents of US dollars.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_amount_usd: Option<i64>,
+    /// The amount, in minor units of the billing currency of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_billing_amount: Option<i64>,
+    /// The ISO 4217 code of the billing currency of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing_currency: Option<String>,
+    /// The ISO 18245 merchant category code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merchant_category_code: Option<String>,
+    /// What kind of transaction it is, e.g. `purchase`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_type: Option<String>,
+    /// Where the transaction was made, e.g. `ecommerce`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_channel: Option<String>,
+    /// How the card details were entered at the point of sale.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pos_entry_mode: Option<String>,
+    /// The bank identification number of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_bin: Option<String>,
+    /// Whether the card was present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_card_present: Option<bool>,
+    /// Whether the transaction is a recurring payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_recurring: Option<bool>,
+    /// Whether the cardholder was authenticated with 3-D Secure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_3ds_authenticated: Option<bool>,
+}
+
+impl TypedEvent for CardAuthorization {
+    const ENDPOINT: Endpoint = Endpoint::CardAuthorization;
+}
+
+/// A card clearing event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardClearing {
+    /// The id of the transaction.
+    pub transaction_id: String,
+    /// When the event happened, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_timestamp: Option<i64>,
+    /// When the transaction was cleared, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearing_date: Option<i64>,
+    /// The amount, in minor units of the transaction currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_amount: Option<i64>,
+    /// The ISO 4217 code of the transaction currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_currency: Option<String>,
+    /// The cleared amount, in minor units of the clearing currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearing_amount: Option<i64>,
+    /// The ISO 4217 code of the clearing currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearing_currency: Option<String>,
+    /// The cleared amount, in minor units of the billing currency of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearing_billing_amount: Option<i64>,
+    /// The ISO 4217 code of the billing currency of the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing_currency: Option<String>,
+    /// What kind of transaction it is, e.g. `purchase`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_type: Option<String>,
+    /// The id of the authorization being cleared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization_id: Option<String>,
+    /// The ISO 18245 merchant category code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merchant_category_code: Option<String>,
+    /// Whether the clearing reverses an earlier one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_reversal: Option<bool>,
+}
+
+impl TypedEvent for CardClearing {
+    const ENDPOINT: Endpoint = Endpoint::CardClearing;
+}
+
+/// A transfer initiation event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransferInitiation {
+    /// The id of the transfer.
+    pub transfer_id: String,
+    /// When the event happened, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_timestamp: Option<i64>,
+    /// The amount transferred, in minor units of the transfer currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_amount: Option<i64>,
+    /// The fee charged for the transfer, in minor units of the transfer currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_fee: Option<i64>,
+    /// The ISO 4217 code of the transfer currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_currency: Option<String>,
+    /// The account number of the sender.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_account_number: Option<String>,
+    /// The account number of the beneficiary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beneficiary_account_number: Option<String>,
+    /// Whether the transfer crosses borders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_international: Option<bool>,
+    /// Whether the sender never transferred to the beneficiary before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_new_beneficiary: Option<bool>,
+}
+
+impl TypedEvent for TransferInitiation {
+    const ENDPOINT: Endpoint = Endpoint::TransferInitiation;
+}
+
+/// A transfer settlement event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransferSettlement {
+    /// The id of the transfer.
+    pub transfer_id: String,
+    /// When the event happened, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_timestamp: Option<i64>,
+    /// When the transfer was settled, in epoch millis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement_date: Option<i64>,
+    /// The amount transferred, in minor units of the transfer currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_amount: Option<i64>,
+    /// The ISO 4217 code of the transfer currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_currency: Option<String>,
+    /// The amount settled, in minor units of the settlement currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement_amount: Option<i64>,
+    /// The ISO 4217 code of the settlement currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement_currency: Option<String>,
+    /// Whether the transfer was settled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_settled: Option<bool>,
+}
+
+impl TypedEvent for TransferSettlement {
+    const ENDPOINT: Endpoint = Endpoint::TransferSettlement;
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::EventValidation;
+
+    #[test]
+    fn read_validated_csv_event() {
+        let event = json!({
+            "key": "k",
+            "account_id": "a1",
+            "account_cards": r#"["c1"]"#,
+            "account_number_of_cards": "1",
+            "account_active": "true",
+        })
+        .validate(&Endpoint::ReferenceDataAccount.schema())
+        .expect("Validated event");
+
+        assert_eq!(
+            Account::from_event(event).expect("Typed event"),
+            Account {
+                account_id: "a1".into(),
+                account_cards: Some(vec!["c1".into()]),
+                account_number_of_cards: Some(1),
+                account_active: Some("true".into()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn read_limits_in_minor_units() {
+        let account = json!({
+            "account_id": "a1",
+            "account_limits": r#"[{"limit_type": "daily", "limit_amount": "500.50", "limit_currency": "EUR"}]"#,
+        })
+        .validate(&Endpoint::ReferenceDataAccount.schema())
+        .expect("Validated account");
+        let card = json!({"card_id": "c1", "card_daily_limit": "1500", "card_currency": "JPY"})
+            .validate(&Endpoint::ReferenceDataCard.schema())
+            .expect("Validated card");
+
+        assert_eq!(
+            Account::from_event(account)
+                .expect("Typed account")
+                .account_limits,
+            Some(vec![AccountLimit {
+                limit_type: "daily".into(),
+                limit_amount: 50050,
+                limit_currency: "EUR".into(),
+            }])
+        );
+        assert_eq!(
+            Card::from_event(card).expect("Typed card"),
+            Card {
+                card_id: "c1".into(),
+                card_daily_limit: Some(1500),
+                card_currency: Some("JPY".into()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn read_customer_addresses() {
+        let customer = json!({
+            "customer_id": "cu1",
+            "customer_addresses": r#"[{"street": "1 Main St", "postal_code": 12345, "is_primary": "yes"}]"#,
+        })
+        .validate(&Endpoint::ReferenceDataCustomer.schema())
+        .expect("Validated customer");
+
+        assert_eq!(
+            Customer::from_event(customer)
+                .expect("Typed customer")
+                .customer_addresses,
+            Some(vec![Address {
+                street: Some("1 Main St".into()),
+                postal_code: Some("12345".into()),
+                is_primary: Some(true),
+                ..Default::default()
+            }])
+        );
+        assert!(Customer::from_event(json!({
+            "customer_id": "cu1",
+            "customer_addresses": [{"street": "1 Main St", "geo": "1.5,2.5"}],
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn omit_absent_fields() {
+        let transfer = TransferSettlement {
+            transfer_id: "t1".into(),
+            settlement_amount: Some(25000),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            transfer.to_event(),
+            json!({"transfer_id": "t1", "settlement_amount": 25000})
+        );
+    }
+
+    #[test]
+    fn refuse_unknown_and_mistyped_fields() {
+        assert!(Card::from_event(json!({"card_id": "c1", "card_colour": "red"})).is_err());
+        assert!(Card::from_event(json!({"card_id": "c1", "card_is_virtual": "no"})).is_err());
+        assert!(Card::from_event(json!({"card_is_virtual": false})).is_err());
+    }
+}
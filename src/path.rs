@@ -0,0 +1,190 @@
+use serde_json::Map;
+
+use crate::Event;
+
+/// The field at `path` in `fields`, where a dotted path like
+/// `merchant.address.country` reaches into nested objects.
+///
+/// A field whose key is the whole path, dots included, is preferred.
+pub fn get<'a>(fields: &'a Map<String, Event>, path: &str) -> Option<&'a Event> {
+    if let Some(value) = fields.get(path) {
+        return Some(value);
+    }
+
+    let (head, rest) = path.split_once('.')?;
+    get(fields.get(head)?.as_object()?, rest)
+}
+
+/// The field at `path` in `fields`, see [`get`].
+pub fn get_mut<'a>(fields: &'a mut Map<String, Event>, path: &str) -> Option<&'a mut Event> {
+    if fields.contains_key(path) {
+        return fields.get_mut(path);
+    }
+
+    let (head, rest) = path.split_once('.')?;
+    get_mut(fields.get_mut(head)?.as_object_mut()?, rest)
+}
+
+/// Removes the field at `path` from `fields`, see [`get`], along with the
+/// objects left empty by its removal.
+pub fn remove(fields: &mut Map<String, Event>, path: &str) -> Option<Event> {
+    if fields.contains_key(path) {
+        return fields.remove(path);
+    }
+
+    let (head, rest) = path.split_once('.')?;
+    let nested = fields.get_mut(head)?.as_object_mut()?;
+    let removed = remove(nested, rest)?;
+
+    if nested.is_empty() {
+        fields.remove(head);
+    }
+
+    Some(removed)
+}
+
+/// Sets the field at the dotted `path` in `fields`, creating the objects
+/// on the way and replacing anything else in their place.
+pub fn insert(fields: &mut Map<String, Event>, path: &str, value: Event) {
+    let Some((head, rest)) = path.split_once('.') else {
+        fields.insert(path.to_string(), value);
+        return;
+    };
+
+    let nested = fields
+        .entry(head)
+        .and_modify(|nested| {
+            if !nested.is_object() {
+                *nested = Event::Object(Map::new());
+            }
+        })
+        .or_insert_with(|| Event::Object(Map::new()));
+
+    if let Event::Object(nested) = nested {
+        insert(nested, rest, value);
+    }
+}
+
+/// The dotted paths of the fields of `fields` that are not objects, or
+/// that are objects `is_leaf` stops at.
+pub fn leaves(fields: &Map<String, Event>, is_leaf: impl Fn(&str) -> bool) -> Vec<String> {
+    fn collect(
+        fields: &Map<String, Event>,
+        prefix: &str,
+        is_leaf: &dyn Fn(&str) -> bool,
+        leaves: &mut Vec<String>,
+    ) {
+        for (key, value) in fields {
+            let path = format!("{prefix}{key}");
+
+            match value {
+                Event::Object(nested) if !is_leaf(&path) => {
+                    collect(nested, &format!("{path}."), is_leaf, leaves)
+                }
+                _ => leaves.push(path),
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    collect(fields, "", &is_leaf, &mut paths);
+    paths
+}
+
+/// Two headers that cannot both be fields because one is nested in the
+/// other, like `merchant` and `merchant.name`.
+pub fn conflict<'a>(headers: &[&'a str]) -> Option<(&'a str, &'a str)> {
+    headers.iter().find_map(|&parent| {
+        headers
+            .iter()
+            .find(|child| {
+                child
+                    .strip_prefix(parent)
+                    .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .map(|&child| (parent, child))
+    })
+}
+
+/// An event with one string field per CSV cell, nested in objects for
+/// dotted headers.
+pub fn from_row<'a>(
+    headers: impl IntoIterator<Item = &'a str>,
+    cells: impl IntoIterator<Item = &'a str>,
+) -> Event {
+    let mut fields = Map::new();
+
+    for (header, cell) in headers.into_iter().zip(cells) {
+        insert(&mut fields, header, cell.into());
+    }
+
+    Event::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn nest_dotted_headers() {
+        let event = from_row(
+            ["id", "merchant.name", "merchant.address.country"],
+            ["t1", "Shop", "SG"],
+        );
+
+        assert_eq!(
+            event,
+            json!({
+                "id": "t1",
+                "merchant": {"name": "Shop", "address": {"country": "SG"}},
+            })
+        );
+    }
+
+    #[test]
+    fn address_nested_fields() {
+        let mut event = json!({"merchant": {"address": {"country": "SG"}}, "a.b": 1});
+        let fields = event.as_object_mut().unwrap();
+
+        assert_eq!(get(fields, "merchant.address.country"), Some(&json!("SG")));
+        assert_eq!(get(fields, "a.b"), Some(&json!(1)));
+        assert_eq!(get(fields, "merchant.name"), None);
+
+        *get_mut(fields, "merchant.address.country").unwrap() = json!("MY");
+        assert_eq!(
+            remove(fields, "merchant.address.country"),
+            Some(json!("MY"))
+        );
+        assert_eq!(event, json!({"a.b": 1}));
+    }
+
+    #[test]
+    fn keep_objects_with_other_fields() {
+        let mut event = json!({"merchant": {"name": "Shop", "address": {"country": "SG"}}});
+
+        remove(event.as_object_mut().unwrap(), "merchant.address.country");
+
+        assert_eq!(event, json!({"merchant": {"name": "Shop"}}));
+    }
+
+    #[test]
+    fn list_leaves() {
+        let event = json!({"id": "t1", "merchant": {"name": "Shop", "geo": {"lat": 1}}});
+
+        assert_eq!(
+            leaves(event.as_object().unwrap(), |path| path == "merchant.geo"),
+            vec!["id", "merchant.geo", "merchant.name"]
+        );
+    }
+
+    #[test]
+    fn find_conflicting_headers() {
+        assert_eq!(
+            conflict(&["merchant", "merchant.name"]),
+            Some(("merchant", "merchant.name"))
+        );
+        assert_eq!(conflict(&["merchant", "merchant_name.x"]), None);
+    }
+}
@@ -0,0 +1,693 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    iter::Peekable,
+    num::NonZeroUsize,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use csv::{ByteRecord, StringRecord};
+use eyre::bail;
+use itertools::Itertools;
+use log::{debug, info, log, warn};
+use rayon::prelude::*;
+
+use crate::{
+    checkpoint::{self, Checkpoint},
+    dead_letter::DeadLetter,
+    error::{FieldError, RowError},
+    path,
+    progress::{self, Progress},
+    report::Report,
+    schema::Schema,
+    summary::ErrorSummary,
+    upload::{accepted, Uploader},
+    Endpoint, Event, EventValidation,
+};
+
+/// How many CSV records are read before being validated in parallel.
+const VALIDATION_CHUNK_SIZE: usize = 10_000;
+
+/// A row rejected by validation, waiting to be dead-lettered in input order:
+/// its line, its record, why it was rejected and on which fields.
+type Reject = (u64, StringRecord, String, String);
+
+/// What a [`Pipeline`] reads, how it validates it and where the events go.
+pub struct Config {
+    /// The input CSV file.
+    pub input: PathBuf,
+    /// The endpoint the events are for.
+    pub endpoint: Endpoint,
+    /// The schema rows are validated against.
+    pub schema: Schema,
+    /// Uploads the valid events, or `None` for a dry run.
+    pub uploader: Option<Uploader>,
+    /// Where a dry run writes its events as NDJSON, stdout by default.
+    pub output: Option<PathBuf>,
+    /// How many events are sent per request to bulk endpoints.
+    pub batch_size: NonZeroUsize,
+    /// How many threads validate rows and upload batches in parallel.
+    pub concurrency: NonZeroUsize,
+    /// Whether to skip the rows already uploaded according to the checkpoint
+    /// of an interrupted run.
+    pub resume: bool,
+    /// Whether to stop at the first invalid row instead of rejecting it.
+    pub fail_fast: bool,
+}
+
+/// A run that stopped before the end of its input.
+#[derive(Debug)]
+pub struct Aborted {
+    /// What the run did before it stopped, with the error that stopped it.
+    pub report: Report,
+    /// Why the run stopped.
+    pub error: eyre::Report,
+}
+
+/// Reads a CSV file in chunks, validates its rows in parallel, uploads the
+/// valid events in batches and dead-letters the rest, checkpointing as it
+/// goes.
+pub struct Pipeline<'a> {
+    config: &'a Config,
+    /// How many fields every input record has.
+    width: usize,
+    columns: Vec<usize>,
+    headers: StringRecord,
+    pool: rayon::ThreadPool,
+    output: Option<Box<dyn Write>>,
+    checkpoint: Option<Checkpoint>,
+    checkpoint_path: PathBuf,
+    dead_letter: DeadLetter,
+    summary: ErrorSummary,
+    report: Report,
+    /// Per-batch lines would tear up the progress bar.
+    batch_log_level: log::Level,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Runs `config` to the end of its input and reports on it.
+    ///
+    /// Stops on invalid headers, I/O errors and failed requests, and on the
+    /// first invalid row if `config.fail_fast` is set, still reporting on
+    /// what was done until then.
+    pub fn run(config: &'a Config) -> Result<Report, Box<Aborted>> {
+        let started = Instant::now();
+
+        let (result, mut report) = match Self::start(config) {
+            Ok((mut pipeline, rows, resume_after)) => {
+                let result = pipeline.process(rows, resume_after);
+                let (finished, report) = pipeline.finish();
+                (result.and(finished), report)
+            }
+            Err(error) => (Err(error), Self::report(config)),
+        };
+
+        report.set_duration(started.elapsed());
+
+        match result {
+            Ok(()) => Ok(report),
+            Err(error) => {
+                report.error = Some(format!("{error:#}"));
+                Err(Box::new(Aborted { report, error }))
+            }
+        }
+    }
+
+    /// The report of a run that has not read anything yet.
+    fn report(config: &Config) -> Report {
+        Report {
+            input: config.input.clone(),
+            endpoint: config.endpoint.to_string(),
+            dry_run: config.uploader.is_none(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks the headers of the input and prepares reading its rows, after
+    /// the checkpoint when resuming.
+    #[allow(clippy::type_complexity)]
+    fn start(
+        config: &'a Config,
+    ) -> eyre::Result<(Self, Progress<csv::ByteRecordsIntoIter<File>>, u64)> {
+        // Records with the wrong number of fields are rejected like invalid
+        // rows rather than failing the run.
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(&config.input)?;
+        let width = reader.byte_headers()?.len();
+        let columns = DeadLetter::input_columns(reader.headers()?);
+        let headers = select(reader.headers()?, &columns);
+
+        debug!("headers: {headers:?}");
+
+        let header_names = headers.iter().collect::<Vec<_>>();
+
+        if let Some((parent, child)) = path::conflict(&header_names) {
+            bail!("Invalid headers: {child} is nested in {parent}, which is a column itself");
+        }
+
+        if let Err(errors) = config.schema.check_headers(&header_names) {
+            bail!("Invalid headers: {}", errors.iter().join("; "));
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.concurrency.get())
+            .build()?;
+
+        let output: Option<Box<dyn Write>> = match (&config.uploader, &config.output) {
+            (Some(_), _) => None,
+            (None, Some(path)) => Some(Box::new(BufWriter::new(File::create(path)?))),
+            (None, None) => Some(Box::new(std::io::stdout().lock())),
+        };
+
+        let sha256 = checkpoint::sha256(&config.input)?;
+
+        let mut checkpoint = config
+            .uploader
+            .as_ref()
+            .map(|_| Checkpoint::new(&config.input, sha256.clone(), config.endpoint));
+
+        let checkpoint_path = Checkpoint::path_for(&config.input);
+        let mut resume_after = None;
+
+        if let (true, Some(checkpoint)) = (config.resume, &mut checkpoint) {
+            match Checkpoint::load(&checkpoint_path)? {
+                Some(saved) => {
+                    saved.ensure_resumable(checkpoint)?;
+                    info!("resuming after line {}", saved.line);
+                    resume_after = Some(saved.line);
+                    checkpoint.line = saved.line;
+                }
+                None => info!("no checkpoint to resume from, starting from the beginning"),
+            }
+        }
+
+        // Only the rejects of the run being resumed are kept, any other reject
+        // file is stale.
+        let dead_letter = match resume_after {
+            Some(_) => DeadLetter::appending(&config.input, &headers),
+            None => DeadLetter::new(&config.input, &headers)?,
+        };
+
+        let rows = Progress::new(reader.into_byte_records(), || {
+            progress::count_rows(&config.input)
+        })?;
+
+        let pipeline = Pipeline {
+            config,
+            width,
+            columns,
+            headers,
+            pool,
+            output,
+            checkpoint,
+            checkpoint_path,
+            dead_letter,
+            summary: ErrorSummary::default(),
+            report: Report {
+                sha256,
+                ..Self::report(config)
+            },
+            batch_log_level: if rows.is_drawn() {
+                log::Level::Debug
+            } else {
+                log::Level::Info
+            },
+        };
+
+        Ok((pipeline, rows, resume_after.unwrap_or(0)))
+    }
+
+    fn process(
+        &mut self,
+        mut rows: Progress<impl Iterator<Item = csv::Result<ByteRecord>>>,
+        resume_after: u64,
+    ) -> eyre::Result<()> {
+        loop {
+            let chunk = rows
+                .by_ref()
+                .take(VALIDATION_CHUNK_SIZE)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if chunk.is_empty() {
+                break;
+            }
+
+            let records = chunk
+                .into_iter()
+                .filter(|record| line_of(record) > resume_after)
+                .collect::<Vec<_>>();
+
+            let Some(last_line) = records.last().map(line_of) else {
+                continue;
+            };
+
+            self.report.rows_read += records.len() as u64;
+
+            let (events, rejects) = self.validate(records)?;
+
+            self.report.rows_valid += events.len() as u64;
+
+            debug!(
+                "events: {:?}",
+                events.iter().map(|(_, _, event)| event).collect::<Vec<_>>()
+            );
+
+            let mut rejects = rejects.into_iter().peekable();
+
+            if let Some(output) = &mut self.output {
+                write_ndjson(&mut *output, events.iter().map(|(_, _, event)| event))?;
+            }
+
+            if self.config.uploader.is_some() {
+                self.upload(&events, &mut rejects)?;
+            }
+
+            self.reject_until(&mut rejects, last_line)?;
+            self.save_checkpoint(last_line)?;
+
+            rows.set_status(format!(
+                "valid {}, rejected {}, uploaded {}, rate limited {:.1?}",
+                self.report.rows_valid,
+                self.report.rows_rejected,
+                self.report.rows_uploaded,
+                self.config
+                    .uploader
+                    .as_ref()
+                    .map_or(Duration::ZERO, Uploader::rate_limit_wait)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates `records` in parallel, but collects them back in input order
+    /// so that errors are reported and rejected deterministically.
+    #[allow(clippy::type_complexity)]
+    fn validate(
+        &mut self,
+        records: Vec<ByteRecord>,
+    ) -> eyre::Result<(Vec<(u64, StringRecord, Event)>, Vec<Reject>)> {
+        let (columns, headers, schema) = (&self.columns, &self.headers, &self.config.schema);
+        let width = self.width;
+
+        let validated = self.pool.install(|| {
+            records
+                .into_par_iter()
+                .map(|record| {
+                    let line = line_of(&record);
+
+                    match read_row(record, width) {
+                        Ok(record) => {
+                            let record = select(&record, columns);
+                            let event = path::from_row(headers, &record).validate(schema);
+
+                            (line, record, event)
+                        }
+                        Err((record, error)) => (line, record, Err(vec![error])),
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut events = Vec::new();
+        let mut rejects = Vec::new();
+
+        for (line, record, event) in validated {
+            match event {
+                Ok(event) => events.push((line, record, event)),
+                Err(errors) => {
+                    self.report.rows_rejected += 1;
+
+                    let reason = errors.iter().join("; ");
+                    let fields = errors.iter().filter_map(FieldError::column).join(";");
+
+                    for error in errors {
+                        let error = RowError { line, error };
+
+                        if self.config.fail_fast {
+                            return Err(error.into());
+                        }
+
+                        self.summary.add(&error);
+                    }
+
+                    rejects.push((line, record, reason, fields));
+                }
+            }
+        }
+
+        Ok((events, rejects))
+    }
+
+    /// Uploads `events` in windows that keep every worker busy with one
+    /// batch, handling the results of a window in input order before the
+    /// next one starts.
+    fn upload(
+        &mut self,
+        events: &[(u64, StringRecord, Event)],
+        rejects: &mut Peekable<impl Iterator<Item = Reject>>,
+    ) -> eyre::Result<()> {
+        let config = self.config;
+        let uploader = config
+            .uploader
+            .as_ref()
+            .expect("Uploading without uploader");
+        let batch_size = config.batch_size.get();
+
+        for window in events.chunks(batch_size * config.concurrency.get()) {
+            let results = self.pool.install(|| {
+                window
+                    .par_chunks(batch_size)
+                    .map(|batch| {
+                        uploader.upload_batch(
+                            config.endpoint,
+                            &batch.iter().map(|(_, _, event)| event).collect::<Vec<_>>(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            for (batch, statuses) in window.chunks(batch_size).zip(results) {
+                let mut batch_uploaded = 0;
+
+                for ((line, record, _), status) in batch.iter().zip(statuses?) {
+                    self.reject_until(rejects, *line)?;
+
+                    *self.report.http_statuses.entry(status).or_default() += 1;
+
+                    if accepted(status) {
+                        batch_uploaded += 1;
+                        debug!("line {line}: {status}");
+                    } else {
+                        warn!("line {line}: {status}");
+                        self.dead_letter
+                            .reject(record, *line, &format!("HTTP {status}"), "")?;
+                    }
+                }
+
+                let batch_last_line = batch[batch.len() - 1].0;
+
+                log!(
+                    self.batch_log_level,
+                    "lines {}-{batch_last_line}: {batch_uploaded} of {} events uploaded, {:.1?} waited for rate limit",
+                    batch[0].0,
+                    batch.len(),
+                    uploader.rate_limit_wait()
+                );
+
+                self.report.rows_uploaded += batch_uploaded;
+                self.save_checkpoint(batch_last_line)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the rows rejected by validation up to `line` to the dead
+    /// letter, so that it stays in input order with rows rejected by Feedzai.
+    fn reject_until(
+        &mut self,
+        rejects: &mut Peekable<impl Iterator<Item = Reject>>,
+        line: u64,
+    ) -> eyre::Result<()> {
+        while let Some((reject_line, record, reason, fields)) =
+            rejects.next_if(|(reject_line, ..)| *reject_line <= line)
+        {
+            self.dead_letter
+                .reject(&record, reject_line, &reason, &fields)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records that every row up to `line` was uploaded or rejected, once
+    /// the rejects are on disk.
+    fn save_checkpoint(&mut self, line: u64) -> eyre::Result<()> {
+        if let Some(checkpoint) = &mut self.checkpoint {
+            self.dead_letter.flush()?;
+            checkpoint.line = line;
+            checkpoint.save(&self.checkpoint_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Logs the outcome and flushes the rejects, whether or not the run
+    /// reached the end of its input.
+    fn finish(self) -> (eyre::Result<()>, Report) {
+        let mut report = self.report;
+
+        info!(
+            "{} valid, {} rejected rows",
+            report.rows_valid, report.rows_rejected
+        );
+        self.summary.log();
+
+        if report.dry_run {
+            info!("dry run: {} events not uploaded", report.rows_valid);
+        } else {
+            info!(
+                "uploaded {} of {} events",
+                report.rows_uploaded, report.rows_valid
+            );
+        }
+
+        report.retries = self.config.uploader.as_ref().map_or(0, Uploader::retries);
+        report.set_errors(&self.summary);
+
+        (self.dead_letter.finish(), report)
+    }
+}
+
+fn line_of(record: &ByteRecord) -> u64 {
+    record.position().map_or(0, csv::Position::line)
+}
+
+/// Writes one JSON event per line.
+fn write_ndjson<'a>(
+    output: impl Write,
+    events: impl IntoIterator<Item = &'a Event>,
+) -> eyre::Result<()> {
+    let mut output = BufWriter::new(output);
+
+    for event in events {
+        serde_json::to_writer(&mut output, event)?;
+        output.write_all(b"\n")?;
+    }
+
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Reads `record` as a row of `width` UTF-8 cells, or fails with the record
+/// as it was read, padded to `width` so that it can still be dead-lettered.
+fn read_row(record: ByteRecord, width: usize) -> Result<StringRecord, (StringRecord, FieldError)> {
+    let (record, reason) = if record.len() == width {
+        match StringRecord::from_byte_record(record) {
+            Ok(record) => return Ok(record),
+            Err(err) => {
+                let reason = format!("field {} is not valid UTF-8", err.utf8_error().field() + 1);
+                (err.into_byte_record(), reason)
+            }
+        }
+    } else {
+        let reason = format!("expected {width} fields, found {}", record.len());
+        (record, reason)
+    };
+
+    let mut cells = record
+        .iter()
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>();
+    cells.resize(cells.len().max(width), "".into());
+
+    Err((StringRecord::from(cells), FieldError::Malformed { reason }))
+}
+
+/// The cells of `record` at `columns`.
+fn select(record: &StringRecord, columns: &[usize]) -> StringRecord {
+    columns.iter().map(|&index| &record[index]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::upload::tests::stub_server;
+
+    #[test]
+    fn write_events_as_ndjson() {
+        let events = [json!({"id": "a1", "count": 1}), json!({"id": "a2"})];
+        let mut output = Vec::new();
+
+        write_ndjson(&mut output, &events).expect("Written events");
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"count\":1,\"id\":\"a1\"}\n{\"id\":\"a2\"}\n"
+        );
+    }
+
+    #[test]
+    fn dry_run_rejects_invalid_rows() {
+        let dir = std::env::temp_dir().join("feedzai-client-pipeline-dry-run");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+        let output = dir.join("accounts.ndjson");
+
+        std::fs::write(
+            &input,
+            "account_id,account_number_of_cards\na1,2\na2,two\na3,\n",
+        )
+        .unwrap();
+
+        let config = Config {
+            input: input.clone(),
+            endpoint: Endpoint::ReferenceDataAccount,
+            schema: Endpoint::ReferenceDataAccount.schema(),
+            uploader: None,
+            output: Some(output.clone()),
+            batch_size: NonZeroUsize::MIN,
+            concurrency: NonZeroUsize::MIN,
+            resume: false,
+            fail_fast: false,
+        };
+
+        let report = Pipeline::run(&config).expect("Finished run");
+        let events = std::fs::read_to_string(&output).unwrap();
+        let rejects = std::fs::read_to_string(DeadLetter::path_for(&input)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((report.rows_read, report.rows_valid), (3, 2));
+        assert_eq!(report.rows_rejected, 1);
+        assert!(report.dry_run);
+        assert_eq!(report.endpoint, "ref_account");
+        assert_eq!(report.error, None);
+        assert_eq!(
+            events,
+            "{\"account_id\":\"a1\",\"account_number_of_cards\":2}\n{\"account_id\":\"a3\"}\n"
+        );
+        assert!(rejects.contains("a2,two,"));
+    }
+
+    #[test]
+    fn resume_upload_after_checkpoint() {
+        let dir = std::env::temp_dir().join("feedzai-client-pipeline-resume");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+
+        std::fs::write(
+            &input,
+            "account_id,account_number_of_cards\na1,1\na2,two\na3,3\n",
+        )
+        .unwrap();
+
+        let config = |url: &str, resume| Config {
+            input: input.clone(),
+            endpoint: Endpoint::ReferenceDataAccount,
+            schema: Endpoint::ReferenceDataAccount.schema(),
+            uploader: Some(Uploader::new(url)),
+            output: None,
+            batch_size: NonZeroUsize::MIN,
+            concurrency: NonZeroUsize::MIN,
+            resume,
+            fail_fast: false,
+        };
+
+        let (url, server) = stub_server(vec![200, 422]);
+        let report = Pipeline::run(&config(&url, false)).expect("Finished run");
+        let posted = server.join().unwrap().len();
+
+        let checkpoint = Checkpoint::load(&Checkpoint::path_for(&input))
+            .unwrap()
+            .expect("Saved checkpoint");
+        let rejects = std::fs::read_to_string(DeadLetter::path_for(&input)).unwrap();
+
+        // Nothing is listening any more, so any request would fail the run.
+        let (url, server) = stub_server(vec![]);
+        server.join().unwrap();
+        let resumed = Pipeline::run(&config(&url, true)).expect("Finished resumed run");
+        let resumed_rejects = std::fs::read_to_string(DeadLetter::path_for(&input)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(posted, 2);
+        assert_eq!(report.rows_uploaded, 1);
+        assert_eq!(report.http_statuses, [(200, 1), (422, 1)].into());
+        assert_eq!(checkpoint.line, 4);
+        assert_eq!(
+            rejects.lines().skip(1).collect::<Vec<_>>(),
+            vec![
+                "a2,two,\"column account_number_of_cards is \"\"two\"\", expected int\",account_number_of_cards,3",
+                "a3,3,HTTP 422,,4",
+            ]
+        );
+        assert_eq!(resumed.rows_read, 0);
+        assert!(resumed.http_statuses.is_empty());
+        assert_eq!(resumed_rejects, rejects);
+    }
+
+    #[test]
+    fn reject_malformed_records() {
+        let dir = std::env::temp_dir().join("feedzai-client-pipeline-malformed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("accounts.csv");
+
+        std::fs::write(
+            &input,
+            b"account_id,account_number_of_cards\na1\na2,2,extra\na3,\xff\na4,4\n",
+        )
+        .unwrap();
+
+        let config = Config {
+            input: input.clone(),
+            endpoint: Endpoint::ReferenceDataAccount,
+            schema: Endpoint::ReferenceDataAccount.schema(),
+            uploader: None,
+            output: Some(dir.join("accounts.ndjson")),
+            batch_size: NonZeroUsize::MIN,
+            concurrency: NonZeroUsize::MIN,
+            resume: false,
+            fail_fast: false,
+        };
+
+        let report = Pipeline::run(&config).expect("Finished run");
+        let rejects = std::fs::read_to_string(DeadLetter::path_for(&input)).unwrap();
+
+        let fail_fast = Pipeline::run(&Config {
+            fail_fast: true,
+            ..config
+        });
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((report.rows_read, report.rows_valid), (4, 1));
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            (report.errors[0].kind, report.errors[0].count),
+            ("malformed", 3)
+        );
+        assert_eq!(report.errors[0].sample_lines, vec![2, 3, 4]);
+        assert_eq!(
+            rejects.lines().skip(1).collect::<Vec<_>>(),
+            vec![
+                "a1,,\"record is malformed: expected 2 fields, found 1\",,2",
+                "a2,2,extra,\"record is malformed: expected 2 fields, found 3\",,3",
+                "a3,\u{fffd},record is malformed: field 2 is not valid UTF-8,,4",
+            ]
+        );
+        let aborted = fail_fast.unwrap_err();
+
+        assert!(aborted
+            .error
+            .to_string()
+            .starts_with("line 2: record is malformed"));
+        assert_eq!(aborted.report.error, Some(aborted.error.to_string()));
+        assert_eq!(aborted.report.rows_read, 4);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read},
+    path::Path,
+};
+
+use tqdm::Tqdm;
+
+/// Input rows, drawn as a progress bar on stderr when it is a terminal so
+/// that logs of scheduled runs stay clean.
+pub struct Progress<I: Iterator> {
+    rows: Option<I>,
+    bar: Option<Tqdm<I::Item, WithTotal<I>>>,
+}
+
+impl<I: Iterator> Progress<I> {
+    /// Wraps `rows`, of which there are about as many as `total` counts.
+    /// They are only counted if the bar is drawn, as counting reads the
+    /// whole input.
+    pub fn new(rows: I, total: impl FnOnce() -> io::Result<usize>) -> io::Result<Self> {
+        if io::stderr().is_terminal() {
+            let bar = tqdm::tqdm(WithTotal {
+                rows,
+                total: total()?,
+            })
+            .desc(Some("rows"));
+
+            Ok(Self {
+                rows: None,
+                bar: Some(bar),
+            })
+        } else {
+            Ok(Self {
+                rows: Some(rows),
+                bar: None,
+            })
+        }
+    }
+
+    /// Whether the progress bar is drawn.
+    pub fn is_drawn(&self) -> bool {
+        self.bar.is_some()
+    }
+
+    /// Shows `status` in front of the bar.
+    pub fn set_status(&mut self, status: impl ToString) {
+        if let Some(bar) = self.bar.take() {
+            self.bar = Some(bar.desc(Some(status)));
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for Progress<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (&mut self.bar, &mut self.rows) {
+            (Some(bar), _) => bar.next(),
+            (None, Some(rows)) => rows.next(),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Lets the progress bar know how many rows to expect.
+pub struct WithTotal<I> {
+    rows: I,
+    total: usize,
+}
+
+impl<I: Iterator> Iterator for WithTotal<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.total))
+    }
+}
+
+/// Counts the rows of a CSV file with a header by counting its lines, which
+/// is fast but overcounts rows containing quoted line breaks.
+pub fn count_rows(path: &Path) -> io::Result<usize> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1 << 16];
+    let mut lines = 0;
+    let mut last = b'\n';
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        lines += buffer[..read].iter().filter(|&&byte| byte == b'\n').count();
+        last = buffer[read - 1];
+    }
+
+    if last != b'\n' {
+        lines += 1;
+    }
+
+    Ok(lines.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_rows_without_header() {
+        let path = std::env::temp_dir().join("feedzai-client-count.csv");
+
+        std::fs::write(&path, "id\na1\na2\n").unwrap();
+        let with_newline = count_rows(&path).unwrap();
+
+        std::fs::write(&path, "id\na1\na2").unwrap();
+        let without_newline = count_rows(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(with_newline, 2);
+        assert_eq!(without_newline, 2);
+    }
+
+    #[test]
+    fn yield_every_row() {
+        let progress = Progress::new(0..5, || Ok(5)).unwrap();
+
+        assert_eq!(progress.collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn count_rows_only_for_drawn_bar() {
+        let counted = std::cell::Cell::new(false);
+
+        let progress = Progress::new(0..5, || {
+            counted.set(true);
+            Ok(5)
+        })
+        .unwrap();
+
+        assert_eq!(counted.get(), progress.is_drawn());
+    }
+}
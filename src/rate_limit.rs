@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// A token bucket shared by all upload workers, spacing requests evenly so
+/// that no more than `max_rps` are sent per second.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+    waited_micros: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(max_rps: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / max_rps),
+            next: Mutex::new(Instant::now()),
+            waited_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Blocks until a request may be sent.
+    pub fn acquire(&self) {
+        let now = Instant::now();
+
+        // Reserve the next free slot while holding the lock, but sleep
+        // without it so other workers can queue up behind us.
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot
+        };
+
+        let wait = slot - now;
+
+        if !wait.is_zero() {
+            self.waited_micros
+                .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// How long workers have waited for the limiter in total.
+    pub fn waited(&self) -> Duration {
+        Duration::from_micros(self.waited_micros.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn space_requests_evenly() {
+        let limiter = RateLimiter::new(100.0);
+        let start = Instant::now();
+
+        for _ in 0..6 {
+            limiter.acquire();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(!limiter.waited().is_zero());
+    }
+
+    #[test]
+    fn share_limit_between_threads() {
+        let limiter = RateLimiter::new(100.0);
+        let start = Instant::now();
+
+        std::thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..3 {
+                        limiter.acquire();
+                    }
+                });
+            }
+        });
+
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+}
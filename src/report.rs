@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::summary::ErrorSummary;
+
+/// A machine-readable account of a run, for schedulers deciding whether to
+/// alert.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Report {
+    /// The CSV file that was read.
+    pub input: PathBuf,
+    /// The SHA-256 of the input, as lowercase hex.
+    pub sha256: String,
+    /// The endpoint the events were validated for.
+    pub endpoint: String,
+    /// Whether events were written out instead of uploaded.
+    pub dry_run: bool,
+    /// How many rows were read, excluding the ones skipped on resume.
+    pub rows_read: u64,
+    /// How many rows passed validation.
+    pub rows_valid: u64,
+    /// How many rows failed validation.
+    pub rows_rejected: u64,
+    /// The validation errors by column and kind.
+    pub errors: Vec<ErrorCount>,
+    /// How many events Feedzai accepted.
+    pub rows_uploaded: u64,
+    /// How many events Feedzai answered with each HTTP status.
+    pub http_statuses: BTreeMap<u16, u64>,
+    /// How many requests were retried.
+    pub retries: u64,
+    /// How long the run took.
+    pub duration_secs: f64,
+    /// How many rows were read per second.
+    pub rows_per_sec: f64,
+    /// Why the run stopped before the end of its input, if it did.
+    pub error: Option<String>,
+}
+
+/// How often one kind of error happened in one column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorCount {
+    /// The column, or `-` for errors about the whole row.
+    pub column: String,
+    /// The kind of error, e.g. `invalid_value`.
+    pub kind: &'static str,
+    /// How many times the error happened.
+    pub count: usize,
+    /// The first few lines the error happened on.
+    pub sample_lines: Vec<u64>,
+}
+
+impl Report {
+    /// Fills in the errors of `summary`.
+    pub fn set_errors(&mut self, summary: &ErrorSummary) {
+        self.errors = summary
+            .groups()
+            .map(|(column, kind, group)| ErrorCount {
+                column: column.to_string(),
+                kind,
+                count: group.count,
+                sample_lines: group.sample_lines.clone(),
+            })
+            .collect();
+    }
+
+    /// Fills in the duration of the run and its throughput.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_secs = duration.as_secs_f64();
+        self.rows_per_sec = if self.duration_secs > 0.0 {
+            self.rows_read as f64 / self.duration_secs
+        } else {
+            0.0
+        };
+    }
+
+    /// Writes the report to `path` as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> eyre::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Whether some rows were rejected, either by validation or by Feedzai.
+    pub fn is_partial_failure(&self) -> bool {
+        self.rows_rejected > 0 || (!self.dry_run && self.rows_uploaded < self.rows_valid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn detect_partial_failure() {
+        let uploaded = Report {
+            rows_read: 2,
+            rows_valid: 2,
+            rows_uploaded: 2,
+            ..Default::default()
+        };
+        let rejected_by_feedzai = Report {
+            rows_uploaded: 1,
+            ..uploaded.clone()
+        };
+        let rejected_by_validation = Report {
+            rows_rejected: 1,
+            ..Default::default()
+        };
+        let dry_run = Report {
+            dry_run: true,
+            rows_valid: 2,
+            ..Default::default()
+        };
+
+        assert!(!uploaded.is_partial_failure());
+        assert!(rejected_by_feedzai.is_partial_failure());
+        assert!(rejected_by_validation.is_partial_failure());
+        assert!(!dry_run.is_partial_failure());
+    }
+
+    #[test]
+    fn serialize_status_histogram() {
+        let report = Report {
+            http_statuses: [(200, 3), (422, 1)].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["http_statuses"],
+            json!({"200": 3, "422": 1})
+        );
+    }
+}
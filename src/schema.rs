@@ -0,0 +1,1313 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    path::Path,
+    str::FromStr,
+};
+
+use eyre::{bail, Context};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    error::FieldError,
+    path,
+    timestamp::{TimestampFormat, TimestampRange, Timezone},
+    Endpoint, Event, EventValidation, Validator,
+};
+
+/// Field rules for one Feedzai endpoint, as written in a schema file.
+///
+/// Rules are applied in declaration order: unknown fields are rejected if
+/// the schema is strict, empty cells are handled, fields are dropped,
+/// required fields are checked and enumerated fields normalized from the raw
+/// CSV values, and the remaining fields are converted to their JSON types.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schema {
+    /// What to do with empty cells of fields without an `empty_fields`
+    /// policy.
+    pub empty: EmptyPolicy,
+    /// What to do with empty cells, per field.
+    pub empty_fields: BTreeMap<String, EmptyPolicy>,
+    /// Fields removed from events.
+    pub drop: Vec<String>,
+    /// Fields every event must have.
+    pub required: Vec<String>,
+    /// The values allowed for each enumerated field.
+    #[serde(rename = "enum")]
+    pub enums: BTreeMap<String, EnumRule>,
+    /// Array fields, written either as a list of fields holding JSON arrays
+    /// of anything or as a table of [`ArrayRule`]s.
+    #[serde(deserialize_with = "array_rules")]
+    pub array: BTreeMap<String, ArrayRule>,
+    /// Integer fields.
+    pub int: Vec<String>,
+    /// Floating point fields.
+    pub float: Vec<String>,
+    /// Amount fields, converted to minor units of the currency each of them
+    /// maps to.
+    pub amount: BTreeMap<String, Currency>,
+    /// String fields.
+    pub str: Vec<String>,
+    /// Boolean fields.
+    pub bool: Vec<String>,
+    /// How booleans may be spelled.
+    pub booleans: Booleans,
+    /// Timestamp fields, converted to epoch millis.
+    pub timestamp: Vec<String>,
+    /// The formats timestamps may be written in, tried in order.
+    /// [`TimestampFormat::defaults`] if empty.
+    pub timestamp_formats: Vec<TimestampFormat>,
+    /// The time zone of timestamps written without an offset, as a fixed
+    /// offset from UTC.
+    pub timezone: Timezone,
+    /// Timestamp fields that may be before 1970, such as birth dates.
+    pub allow_before_1970: Vec<String>,
+    /// Timestamp fields that may be in the future, such as expiry dates.
+    pub allow_future: Vec<String>,
+    /// Whether to reject fields not mentioned by any other rule.
+    pub strict: bool,
+}
+
+/// What to do with a field whose CSV cell is empty, written in schemas as
+/// `keep`, `omit`, `null`, `reject` or a table like `{ default = "0" }`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyPolicy {
+    /// Keep the empty string, to be validated like any other value.
+    #[default]
+    Keep,
+    /// Leave the field out of the event.
+    Omit,
+    /// Send JSON `null`, which conversions leave alone.
+    Null,
+    /// Use this text instead, validated as if it was the cell.
+    Default(String),
+    /// Reject the row.
+    Reject,
+}
+
+/// The currency of an amount field, written in schemas as the column holding
+/// it, e.g. `"transaction_currency"`, or as a table like
+/// `{ currency = "USD" }` for amounts always in the same currency.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Currency {
+    /// The column holding the ISO 4217 code of each row's currency.
+    Column(String),
+    /// The ISO 4217 code of every row's currency.
+    Fixed {
+        /// The ISO 4217 code.
+        currency: String,
+    },
+}
+
+impl Currency {
+    /// The column holding the currency, if it varies per row.
+    pub fn column(&self) -> Option<&String> {
+        match self {
+            Currency::Column(column) => Some(column),
+            Currency::Fixed { .. } => None,
+        }
+    }
+}
+
+/// The values allowed for an enumerated field, written in schemas either as
+/// a list of values or as a table of `values`, `aliases` and
+/// `case_insensitive`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "EnumRuleDef")]
+pub struct EnumRule {
+    /// The values sent to Feedzai.
+    pub values: Vec<String>,
+    /// Other spellings of values, e.g. `Y` for `true`.
+    pub aliases: BTreeMap<String, String>,
+    /// Whether values and aliases match regardless of ASCII case.
+    pub case_insensitive: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnumRuleDef {
+    Values(Vec<String>),
+    Table(EnumTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnumTable {
+    values: Vec<String>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    case_insensitive: bool,
+}
+
+impl TryFrom<EnumRuleDef> for EnumRule {
+    type Error = String;
+
+    fn try_from(def: EnumRuleDef) -> Result<Self, Self::Error> {
+        let rule = match def {
+            EnumRuleDef::Values(values) => EnumRule::from(values),
+            EnumRuleDef::Table(table) => EnumRule {
+                values: table.values,
+                aliases: table.aliases,
+                case_insensitive: table.case_insensitive,
+            },
+        };
+
+        if let Some((alias, value)) = rule
+            .aliases
+            .iter()
+            .find(|(_, value)| !rule.values.contains(value))
+        {
+            return Err(format!("alias {alias:?} is for unknown value {value:?}"));
+        }
+
+        Ok(rule)
+    }
+}
+
+impl From<Vec<String>> for EnumRule {
+    fn from(values: Vec<String>) -> Self {
+        Self {
+            values,
+            ..Default::default()
+        }
+    }
+}
+
+impl EnumRule {
+    /// The value `s` stands for, if it is allowed.
+    pub fn resolve(&self, s: &str) -> Option<&str> {
+        let matches = |candidate: &str| {
+            candidate == s || self.case_insensitive && candidate.eq_ignore_ascii_case(s)
+        };
+
+        self.values
+            .iter()
+            .find(|value| matches(value))
+            .or_else(|| {
+                self.aliases
+                    .iter()
+                    .find(|(alias, _)| matches(alias))
+                    .map(|(_, value)| value)
+            })
+            .map(String::as_str)
+    }
+
+    /// The values and aliases, for error messages.
+    pub fn spellings(&self) -> Vec<String> {
+        self.values
+            .iter()
+            .chain(self.aliases.keys())
+            .cloned()
+            .collect()
+    }
+}
+
+/// How an array field is written and what its elements are, written in
+/// schemas either as a type like `array<int>` or as a table of `type`,
+/// `delimiter` and `schema`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(try_from = "ArrayRuleDef")]
+pub struct ArrayRule {
+    /// The type of the elements.
+    pub elements: ElementType,
+    /// What separates elements in cells that are not JSON arrays, e.g. `|`
+    /// for `card1|card2`.
+    pub delimiter: Option<String>,
+    /// The rules object elements are validated with, as if each of them
+    /// was a CSV row.
+    pub schema: Schema,
+}
+
+/// The type of the elements of an array field.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ElementType {
+    /// Anything, written as `array`.
+    #[default]
+    Any,
+    /// Strings, written as `array<str>`.
+    Str,
+    /// Integers, written as `array<int>`.
+    Int,
+    /// Floating point numbers, written as `array<float>`.
+    Float,
+    /// Booleans, spelled as in the element schema, written as
+    /// `array<bool>`.
+    Bool,
+    /// Objects, written as `array<object>`.
+    Object,
+}
+
+impl FromStr for ElementType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "array" => Ok(ElementType::Any),
+            "array<str>" => Ok(ElementType::Str),
+            "array<int>" => Ok(ElementType::Int),
+            "array<float>" => Ok(ElementType::Float),
+            "array<bool>" => Ok(ElementType::Bool),
+            "array<object>" => Ok(ElementType::Object),
+            _ => Err(format!(
+                "unknown array type {s:?}, expected array or array<str|int|float|bool|object>"
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ArrayRuleDef {
+    Type(String),
+    Table(Box<ArrayTable>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArrayTable {
+    #[serde(rename = "type")]
+    elements: Option<String>,
+    delimiter: Option<String>,
+    #[serde(default)]
+    schema: Schema,
+}
+
+impl TryFrom<ArrayRuleDef> for ArrayRule {
+    type Error = String;
+
+    fn try_from(def: ArrayRuleDef) -> Result<Self, Self::Error> {
+        match def {
+            ArrayRuleDef::Type(elements) => Ok(ArrayRule {
+                elements: elements.parse()?,
+                ..Default::default()
+            }),
+            ArrayRuleDef::Table(table) => Ok(ArrayRule {
+                elements: table.elements.as_deref().unwrap_or("array").parse()?,
+                delimiter: table.delimiter.filter(|delimiter| !delimiter.is_empty()),
+                schema: table.schema,
+            }),
+        }
+    }
+}
+
+/// Reads array rules from either a list of fields or a table of rules.
+fn array_rules<'de, D>(deserializer: D) -> Result<BTreeMap<String, ArrayRule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ArrayRules {
+        Fields(Vec<String>),
+        Rules(BTreeMap<String, ArrayRule>),
+    }
+
+    Ok(match ArrayRules::deserialize(deserializer)? {
+        ArrayRules::Fields(fields) => fields
+            .into_iter()
+            .map(|field| (field, ArrayRule::default()))
+            .collect(),
+        ArrayRules::Rules(rules) => rules,
+    })
+}
+
+/// The spellings of booleans, matched regardless of ASCII case unless
+/// strict.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Booleans {
+    /// Spellings of `true`.
+    pub truthy: Vec<String>,
+    /// Spellings of `false`.
+    pub falsy: Vec<String>,
+    /// Whether to only accept exactly `true` and `false`.
+    pub strict: bool,
+}
+
+impl Default for Booleans {
+    fn default() -> Self {
+        Self {
+            truthy: ["true", "yes", "y", "1"].map(String::from).to_vec(),
+            falsy: ["false", "no", "n", "0"].map(String::from).to_vec(),
+            strict: false,
+        }
+    }
+}
+
+impl Booleans {
+    /// The boolean `s` spells, if any.
+    pub fn parse(&self, s: &str) -> Option<bool> {
+        if self.strict {
+            return s.parse().ok();
+        }
+
+        let spells = |spellings: &[String]| spellings.iter().any(|b| b.eq_ignore_ascii_case(s));
+
+        if spells(&self.truthy) {
+            Some(true)
+        } else if spells(&self.falsy) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// The accepted spellings, for error messages.
+    pub fn spellings(&self) -> Vec<String> {
+        if self.strict {
+            return vec!["true".into(), "false".into()];
+        }
+
+        self.truthy.iter().chain(&self.falsy).cloned().collect()
+    }
+}
+
+impl Schema {
+    /// Loads a schema from a `.toml` or `.json` file.
+    pub fn from_path(path: &Path) -> eyre::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read schema {}", path.display()))?;
+
+        match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => toml::from_str(&content).map_err(eyre::Report::from),
+            Some("json") => serde_json::from_str(&content).map_err(eyre::Report::from),
+            _ => bail!("Schema {} is not a TOML or JSON file", path.display()),
+        }
+        .context(format!("Invalid schema {}", path.display()))
+    }
+
+    /// The schema embedded in the binary for `endpoint`.
+    pub fn builtin(endpoint: Endpoint) -> Self {
+        let content = match endpoint {
+            Endpoint::ReferenceDataAccount => include_str!("../schemas/ref_account.toml"),
+            Endpoint::ReferenceDataCard => include_str!("../schemas/ref_card.toml"),
+            Endpoint::ReferenceDataCustomer => include_str!("../schemas/ref_customer.toml"),
+            Endpoint::ReferenceDataDevice => include_str!("../schemas/ref_device.toml"),
+            Endpoint::CardAuthorization => include_str!("../schemas/card_auth.toml"),
+            Endpoint::CardClearing => include_str!("../schemas/card_clear.toml"),
+            Endpoint::TransferInitiation => include_str!("../schemas/transfer_init.toml"),
+            Endpoint::TransferSettlement => include_str!("../schemas/transfer_settle.toml"),
+        };
+
+        toml::from_str(content).expect("Valid built-in schema")
+    }
+
+    /// Every column mentioned by a rule.
+    pub fn columns(&self) -> BTreeSet<&str> {
+        self.empty_fields
+            .keys()
+            .chain(&self.drop)
+            .chain(&self.required)
+            .chain(self.enums.keys())
+            .chain(self.array.keys())
+            .chain(&self.int)
+            .chain(&self.float)
+            .chain(self.amount.keys())
+            .chain(self.amount.values().filter_map(Currency::column))
+            .chain(&self.str)
+            .chain(&self.bool)
+            .chain(&self.timestamp)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Checks the header row of a CSV file before any record is read, so
+    /// that a file missing a required column, or with an unknown one in
+    /// strict mode, fails as a whole.
+    pub fn check_headers(&self, headers: &[impl AsRef<str>]) -> Result<(), Vec<FieldError>> {
+        let headers = headers.iter().map(AsRef::as_ref).collect::<BTreeSet<_>>();
+        let mut errors = Vec::new();
+
+        for key in &self.required {
+            if !headers.contains(key.as_str()) {
+                errors.push(FieldError::Missing {
+                    column: key.clone(),
+                });
+            }
+        }
+
+        if self.strict {
+            let columns = self.columns();
+
+            for header in headers.difference(&columns) {
+                errors.push(FieldError::Unknown {
+                    column: header.to_string(),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validator for Schema {
+    fn validate(&self, mut event: Event) -> Result<Event, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let obj = event.as_object().ok_or(vec![FieldError::NotAnObject])?;
+
+        if self.strict {
+            let columns = self.columns();
+
+            for path in path::leaves(obj, |path| columns.contains(path)) {
+                if !columns.contains(path.as_str()) {
+                    errors.push(FieldError::Unknown { column: path });
+                }
+            }
+        }
+
+        for path in path::leaves(obj, |_| false) {
+            let policy = self.empty_fields.get(&path).unwrap_or(&self.empty);
+            errors.extend(event.empty_fields(&[path], policy).err());
+        }
+
+        event.drop_fields(&self.drop).map_err(|error| vec![error])?;
+
+        for key in &self.required {
+            errors.extend(event.require(key).err());
+        }
+
+        for (key, rule) in &self.enums {
+            errors.extend(event.enum_fields(&[key], rule).err());
+        }
+
+        for (key, rule) in &self.array {
+            errors.extend(event.array_fields_with(&[key], rule).err());
+        }
+
+        for key in &self.int {
+            errors.extend(event.int_fields(&[key]).err());
+        }
+
+        for key in &self.float {
+            errors.extend(event.float_fields(&[key]).err());
+        }
+
+        for (key, currency) in &self.amount {
+            errors.extend(event.amount_fields(&[key], currency).err());
+        }
+
+        for key in &self.str {
+            errors.extend(event.str_fields(&[key]).err());
+        }
+
+        for key in &self.bool {
+            errors.extend(event.bool_fields_with(&[key], &self.booleans).err());
+        }
+
+        let defaults;
+        let formats = if self.timestamp_formats.is_empty() {
+            defaults = TimestampFormat::defaults();
+            &defaults
+        } else {
+            &self.timestamp_formats
+        };
+
+        for key in &self.timestamp {
+            errors.extend(
+                event
+                    .timestamp_fields(
+                        &[key],
+                        formats,
+                        self.timezone.0,
+                        TimestampRange {
+                            before_1970: self.allow_before_1970.contains(key),
+                            future: self.allow_future.contains(key),
+                        },
+                    )
+                    .err(),
+            );
+        }
+
+        if errors.is_empty() {
+            Ok(event)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn validate_reference_data_account() {
+        let event = json!({
+            "key": "k",
+            "account_id": "a1",
+            "account_cards": r#"["c1"]"#,
+            "account_number_of_cards": "1",
+            "account_open_date": "2023-11-14T22:13:20Z",
+            "account_active": "true",
+        });
+
+        let event = Endpoint::ReferenceDataAccount
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "account_id": "a1",
+                "account_cards": ["c1"],
+                "account_number_of_cards": 1,
+                "account_open_date": 1_700_000_000_000_i64,
+                "account_active": "true",
+            })
+        );
+    }
+
+    #[test]
+    fn read_account_open_date_in_epoch_seconds() {
+        let event = Endpoint::ReferenceDataAccount
+            .schema()
+            .validate(json!({"account_id": "a1", "account_open_date": "1700000000"}))
+            .expect("Validated event");
+
+        assert_eq!(event["account_open_date"], json!(1_700_000_000_000_i64));
+    }
+
+    #[test]
+    fn accept_birth_dates_before_1970_and_future_expiry_dates() {
+        let customer = Endpoint::ReferenceDataCustomer
+            .schema()
+            .validate(json!({"customer_id": "u1", "customer_birth_date": "1965-05-01"}))
+            .expect("Validated customer");
+        let card = Endpoint::ReferenceDataCard
+            .schema()
+            .validate(json!({"card_id": "c1", "card_expiry_date": "2030-01-01"}))
+            .expect("Validated card");
+
+        assert_eq!(customer["customer_birth_date"], json!(-147_398_400_000_i64));
+        assert_eq!(card["card_expiry_date"], json!(1_893_456_000_000_i64));
+        assert_eq!(
+            Endpoint::ReferenceDataCustomer
+                .schema()
+                .validate(json!({"customer_id": "u1", "customer_registration_date": "1965-05-01"})),
+            Err(vec![FieldError::OutOfRange {
+                column: "customer_registration_date".into(),
+                value: "1965-05-01".into(),
+            }])
+        );
+    }
+
+    #[test]
+    fn validate_reference_data_card() {
+        let event = json!({
+            "event_external_id": "e1",
+            "card_id": "c1",
+            "card_expiry_date": "1767225600000",
+            "card_daily_limit": "500.5",
+            "card_currency": "USD",
+            "card_is_virtual": "false",
+        });
+
+        let event = Endpoint::ReferenceDataCard
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "card_id": "c1",
+                "card_expiry_date": 1767225600000_i64,
+                "card_daily_limit": 50050,
+                "card_currency": "USD",
+                "card_is_virtual": false,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_reference_data_customer() {
+        let event = json!({
+            "customer_id": "cu1",
+            "customer_zip_code": "01234",
+            "customer_is_pep": "true",
+        });
+
+        let event = Endpoint::ReferenceDataCustomer
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "customer_id": "cu1",
+                "customer_zip_code": "01234",
+                "customer_is_pep": true,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_reference_data_device() {
+        let event = json!({
+            "device_id": "d1",
+            "device_customers": r#"["cu1","cu2"]"#,
+            "device_latitude": "1.29",
+            "device_is_rooted": "false",
+        });
+
+        let event = Endpoint::ReferenceDataDevice
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "device_id": "d1",
+                "device_customers": ["cu1", "cu2"],
+                "device_latitude": 1.29,
+                "device_is_rooted": false,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_card_authorization() {
+        let event = json!({
+            "key": "k",
+            "transaction_id": "tx1",
+            "event_timestamp": "1700000000000",
+            "transaction_amount": "10.10",
+            "transaction_currency": "EUR",
+            "transaction_amount_usd": "11.05",
+            "merchant_category_code": "5411",
+            "is_card_present": "true",
+        });
+
+        let event = Endpoint::CardAuthorization
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "transaction_id": "tx1",
+                "event_timestamp": 1700000000000_i64,
+                "transaction_amount": 1010,
+                "transaction_currency": "EUR",
+                "transaction_amount_usd": 1105,
+                "merchant_category_code": "5411",
+                "is_card_present": true,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_card_clearing() {
+        let event = json!({
+            "transaction_id": "tx1",
+            "clearing_date": "1700000000000",
+            "clearing_amount": "12.5",
+            "clearing_currency": "SGD",
+            "is_reversal": "false",
+        });
+
+        let event = Endpoint::CardClearing
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "transaction_id": "tx1",
+                "clearing_date": 1700000000000_i64,
+                "clearing_amount": 1250,
+                "clearing_currency": "SGD",
+                "is_reversal": false,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_transfer_initiation() {
+        let event = json!({
+            "transfer_id": "t1",
+            "transfer_amount": "250",
+            "transfer_currency": "MYR",
+            "sender_account_number": "000123",
+            "is_international": "true",
+        });
+
+        let event = Endpoint::TransferInitiation
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "transfer_id": "t1",
+                "transfer_amount": 25000,
+                "transfer_currency": "MYR",
+                "sender_account_number": "000123",
+                "is_international": true,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_transfer_settlement() {
+        let event = json!({
+            "transfer_id": "t1",
+            "settlement_date": "1700000000000",
+            "settlement_amount": "250.00",
+            "settlement_currency": "MYR",
+            "is_settled": "true",
+        });
+
+        let event = Endpoint::TransferSettlement
+            .schema()
+            .validate(event)
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "transfer_id": "t1",
+                "settlement_date": 1700000000000_i64,
+                "settlement_amount": 25000,
+                "settlement_currency": "MYR",
+                "is_settled": true,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_required_fields() {
+        let schema = Schema {
+            required: vec!["account_id".into()],
+            ..Default::default()
+        };
+
+        assert!(schema.validate(json!({"account_id": "a1"})).is_ok());
+        assert!(schema.validate(json!({})).is_err());
+    }
+
+    #[test]
+    fn require_endpoint_ids() {
+        for endpoint in Endpoint::value_variants() {
+            let errors = endpoint.schema().validate(json!({})).unwrap_err();
+
+            assert_eq!(
+                errors,
+                vec![FieldError::Missing {
+                    column: endpoint.id_field().into()
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn reject_unknown_fields_in_strict_mode() {
+        let schema = Schema {
+            drop: vec!["key".into()],
+            int: vec!["amount".into()],
+            strict: true,
+            ..Default::default()
+        };
+
+        assert!(schema.validate(json!({"key": "k", "amount": "5"})).is_ok());
+        assert_eq!(
+            schema.validate(json!({"amount": "5", "colour": "red"})),
+            Err(vec![FieldError::Unknown {
+                column: "colour".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn validate_nested_fields() {
+        let schema = Schema {
+            drop: vec!["merchant.key".into()],
+            required: vec!["merchant.name".into()],
+            float: vec!["merchant.geo.lat".into()],
+            str: vec!["merchant.name".into()],
+            strict: true,
+            ..Default::default()
+        };
+
+        let event = crate::path::from_row(
+            ["merchant.key", "merchant.name", "merchant.geo.lat"],
+            ["k", "Shop", "1.29"],
+        );
+
+        assert_eq!(
+            schema.validate(event),
+            Ok(json!({"merchant": {"name": "Shop", "geo": {"lat": 1.29}}}))
+        );
+        assert_eq!(
+            schema.validate(json!({"merchant": {"name": "Shop", "city": "SG"}})),
+            Err(vec![FieldError::Unknown {
+                column: "merchant.city".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn handle_empty_cells_per_field() {
+        let schema: Schema = toml::from_str(
+            r#"
+            empty = "null"
+            int = ["count", "limit"]
+            str = ["name", "status"]
+
+            [empty_fields]
+            name = "omit"
+            limit = { default = "100" }
+            status = "reject"
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            schema.validate(json!({"count": "", "limit": "", "name": ""})),
+            Ok(json!({"count": null, "limit": 100}))
+        );
+        assert_eq!(
+            schema.validate(json!({"status": ""})),
+            Err(vec![FieldError::Empty {
+                column: "status".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn reject_required_and_currency_cells_emptied_to_null() {
+        let schema: Schema = toml::from_str(
+            r#"
+            empty = "null"
+            required = ["id"]
+
+            [amount]
+            amount = "currency"
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            schema.validate(json!({"id": "", "amount": "10", "currency": ""})),
+            Err(vec![
+                FieldError::Missing {
+                    column: "id".into()
+                },
+                FieldError::Missing {
+                    column: "currency".into()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn omit_empty_cells_of_builtin_schemas() {
+        let schema = Endpoint::ReferenceDataCard.schema();
+
+        assert_eq!(
+            schema.validate(json!({"card_id": "c1", "card_daily_limit": "", "card_status": ""})),
+            Ok(json!({"card_id": "c1"}))
+        );
+        assert_eq!(
+            schema.validate(json!({"card_id": ""})),
+            Err(vec![FieldError::Missing {
+                column: "card_id".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn omit_objects_of_empty_nested_cells() {
+        let schema = Schema {
+            empty: EmptyPolicy::Omit,
+            ..Default::default()
+        };
+
+        let event = crate::path::from_row(
+            ["id", "merchant.name", "merchant.address.country"],
+            ["t1", "", ""],
+        );
+
+        assert_eq!(schema.validate(event), Ok(json!({"id": "t1"})));
+    }
+
+    #[test]
+    fn check_headers_before_reading_records() {
+        let mut schema = Schema::builtin(Endpoint::ReferenceDataAccount);
+
+        assert!(schema.check_headers(&["account_id", "colour"]).is_ok());
+        assert_eq!(
+            schema.check_headers(&["key", "account_active"]),
+            Err(vec![FieldError::Missing {
+                column: "account_id".into()
+            }])
+        );
+
+        schema.strict = true;
+
+        assert_eq!(
+            schema.check_headers(&["account_id", "colour"]),
+            Err(vec![FieldError::Unknown {
+                column: "colour".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn validate_enum_fields() {
+        let schema = Schema {
+            enums: [(
+                "status".to_string(),
+                EnumRule::from(vec!["open".to_string(), "closed".to_string()]),
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        assert!(schema.validate(json!({"status": "open"})).is_ok());
+        assert!(schema.validate(json!({"status": "opne"})).is_err());
+    }
+
+    #[test]
+    fn normalize_account_active() {
+        let schema = Endpoint::ReferenceDataAccount.schema();
+
+        for (active, expected) in [("Y", "true"), ("n", "false"), ("TRUE", "true")] {
+            let event = schema
+                .validate(json!({"account_id": "a1", "account_active": active}))
+                .expect("Validated event");
+
+            assert_eq!(event["account_active"], expected);
+        }
+    }
+
+    #[test]
+    fn parse_lenient_booleans() {
+        let schema = Schema {
+            bool: vec!["active".into()],
+            ..Default::default()
+        };
+
+        for (active, expected) in [("TRUE", true), ("Y", true), ("1", true), ("no", false)] {
+            assert_eq!(
+                schema.validate(json!({"active": active})),
+                Ok(json!({"active": expected}))
+            );
+        }
+
+        assert_eq!(
+            schema.validate(json!({"active": "maybe"})),
+            Err(vec![FieldError::NotAllowed {
+                column: "active".into(),
+                value: "maybe".into(),
+                allowed: ["true", "yes", "y", "1", "false", "no", "n", "0"]
+                    .map(String::from)
+                    .to_vec(),
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_strict_and_custom_booleans() {
+        let strict: Schema = toml::from_str(
+            r#"
+            bool = ["active"]
+            booleans = { strict = true }
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert!(strict.validate(json!({"active": "true"})).is_ok());
+        assert!(strict.validate(json!({"active": "TRUE"})).is_err());
+
+        let custom: Schema = toml::from_str(
+            r#"
+            bool = ["active"]
+            booleans = { truthy = ["S"], falsy = ["N"] }
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            custom.validate(json!({"active": "s"})),
+            Ok(json!({"active": true}))
+        );
+        assert!(custom.validate(json!({"active": "true"})).is_err());
+    }
+
+    #[test]
+    fn load_array_rules() {
+        let fields: Schema = toml::from_str(r#"array = ["cards"]"#).expect("Valid schema");
+
+        assert_eq!(
+            fields.validate(json!({"cards": r#"["c1", 2]"#})),
+            Ok(json!({"cards": ["c1", 2]}))
+        );
+
+        let rules: Schema = toml::from_str(
+            r#"
+            [array]
+            cards = { type = "array<str>", delimiter = "|" }
+            flags = "array<bool>"
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            rules.validate(json!({"cards": "c1|c2", "flags": r#"["Y", false]"#})),
+            Ok(json!({"cards": ["c1", "c2"], "flags": [true, false]}))
+        );
+        assert!(toml::from_str::<Schema>(r#"array = { cards = "array<card>" }"#).is_err());
+    }
+
+    #[test]
+    fn validate_account_arrays() {
+        let event = Endpoint::ReferenceDataAccount
+            .schema()
+            .validate(json!({
+                "account_id": "a1",
+                "account_cards": "c1|c2",
+                "account_limits": r#"[{"limit_type": "daily", "limit_amount": 500.5, "limit_currency": "EUR"}]"#,
+            }))
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "account_id": "a1",
+                "account_cards": ["c1", "c2"],
+                "account_limits": [{"limit_type": "daily", "limit_amount": 50050, "limit_currency": "EUR"}],
+            })
+        );
+
+        let errors = Endpoint::ReferenceDataAccount
+            .schema()
+            .validate(json!({
+                "account_id": "a1",
+                "account_limits": r#"[{"limit_type": "daily", "limit": 5}]"#,
+            }))
+            .unwrap_err();
+
+        assert_eq!(errors[0].column(), Some("account_limits[0].limit"));
+    }
+
+    #[test]
+    fn load_enum_rules() {
+        let schema: Schema = toml::from_str(
+            r#"
+            [enum]
+            status = ["open", "closed"]
+            active = { values = ["true", "false"], aliases = { Y = "true" }, case_insensitive = true }
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            schema.validate(json!({"status": "open", "active": "y"})),
+            Ok(json!({"status": "open", "active": "true"}))
+        );
+        assert!(schema.validate(json!({"status": "Open"})).is_err());
+        assert!(toml::from_str::<Schema>(
+            r#"enum.active = { values = ["true"], aliases = { N = "false" } }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn load_schema_from_json_file() {
+        let path = std::env::temp_dir().join("feedzai-client-schema-test.json");
+        std::fs::write(&path, r#"{"drop": ["key"], "int": ["amount"]}"#).unwrap();
+
+        let schema = Schema::from_path(&path).expect("Loaded schema");
+        let event = schema
+            .validate(json!({"key": "k", "amount": "5"}))
+            .expect("Validated event");
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(event, json!({"amount": 5}));
+    }
+
+    #[test]
+    fn load_timestamp_formats_and_timezone() {
+        let schema: Schema = toml::from_str(
+            r#"
+            timestamp = ["opened"]
+            timestamp_formats = ["epoch_seconds", "[day]/[month]/[year]"]
+            timezone = "+08:00"
+            "#,
+        )
+        .expect("Valid schema");
+
+        let event = schema
+            .validate(json!({"opened": "15/11/2023"}))
+            .expect("Validated event");
+
+        assert_eq!(event, json!({"opened": 1_699_977_600_000_i64}));
+        assert_eq!(
+            schema.validate(json!({"opened": "1700000000"})),
+            Ok(json!({"opened": 1_700_000_000_000_i64}))
+        );
+        assert!(toml::from_str::<Schema>(r#"timestamp_formats = ["[yeer]"]"#).is_err());
+    }
+
+    #[test]
+    fn convert_amounts_in_row_currency() {
+        let schema: Schema = toml::from_str(
+            r#"
+            str = ["currency"]
+
+            [amount]
+            amount = "currency"
+            "#,
+        )
+        .expect("Valid schema");
+
+        assert_eq!(
+            schema.validate(json!({"amount": "10.10", "currency": "EUR"})),
+            Ok(json!({"amount": 1010, "currency": "EUR"}))
+        );
+        assert_eq!(
+            schema.validate(json!({"amount": "1500", "currency": "JPY"})),
+            Ok(json!({"amount": 1500, "currency": "JPY"}))
+        );
+        assert!(schema
+            .validate(json!({"amount": "10.10", "currency": "JPY"}))
+            .is_err());
+    }
+
+    #[test]
+    fn validate_customer_addresses() {
+        let event = Endpoint::ReferenceDataCustomer
+            .schema()
+            .validate(json!({
+                "customer_id": "cu1",
+                "customer_addresses": json!([{
+                    "street": "1 Main St",
+                    "postal_code": 12345,
+                    "is_primary": "yes",
+                }])
+                .to_string(),
+            }))
+            .expect("Validated event");
+
+        assert_eq!(
+            event["customer_addresses"],
+            json!([{
+                "street": "1 Main St",
+                "postal_code": "12345",
+                "is_primary": true,
+            }])
+        );
+
+        let errors = Endpoint::ReferenceDataCustomer
+            .schema()
+            .validate(json!({
+                "customer_id": "cu1",
+                "customer_addresses": r#"[{"street": "1 Main St", "floor": 2}]"#,
+            }))
+            .unwrap_err();
+
+        assert_eq!(errors[0].column(), Some("customer_addresses[0].floor"));
+    }
+
+    #[test]
+    fn normalize_builtin_enums() {
+        let event = Endpoint::CardAuthorization
+            .schema()
+            .validate(json!({
+                "transaction_id": "tx1",
+                "transaction_type": "Purchase",
+                "transaction_channel": "ECOMMERCE",
+                "pos_entry_mode": "05",
+            }))
+            .expect("Validated event");
+
+        assert_eq!(
+            event,
+            json!({
+                "transaction_id": "tx1",
+                "transaction_type": "purchase",
+                "transaction_channel": "ecommerce",
+                "pos_entry_mode": "chip",
+            })
+        );
+
+        let errors = Endpoint::ReferenceDataCard
+            .schema()
+            .validate(json!({"card_id": "c1", "card_status": "frozen"}))
+            .unwrap_err();
+
+        assert_eq!(errors[0].kind(), "not_allowed");
+        assert_eq!(errors[0].column(), Some("card_status"));
+    }
+
+    #[test]
+    fn reject_amounts_too_precise_for_row_currency() {
+        let errors = Endpoint::CardAuthorization
+            .schema()
+            .validate(json!({
+                "transaction_id": "tx1",
+                "transaction_amount": "10.10",
+                "transaction_currency": "JPY",
+            }))
+            .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![FieldError::TooPrecise {
+                column: "transaction_amount".into(),
+                value: "10.10".into(),
+                currency: "JPY".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reject_unknown_schema_rules() {
+        let path = std::env::temp_dir().join("feedzai-client-schema-unknown.toml");
+        std::fs::write(&path, r#"integer = ["amount"]"#).unwrap();
+
+        let result = Schema::from_path(&path);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn collect_every_field_error() {
+        let schema = Schema {
+            required: vec!["id".into()],
+            int: vec!["count".into()],
+            bool: vec!["active".into()],
+            ..Default::default()
+        };
+
+        let errors = schema
+            .validate(json!({"count": "x", "active": "maybe"}))
+            .unwrap_err();
+
+        assert_eq!(
+            errors.iter().map(|e| e.column()).collect::<Vec<_>>(),
+            vec![Some("id"), Some("count"), Some("active")]
+        );
+    }
+}
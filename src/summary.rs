@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+
+use crate::error::RowError;
+
+/// How many line numbers to keep as samples for each group of errors.
+const SAMPLE_LINES: usize = 5;
+
+/// Validation errors of a file, grouped by column and kind of error.
+#[derive(Debug, Default)]
+pub struct ErrorSummary {
+    groups: BTreeMap<(String, &'static str), ErrorGroup>,
+}
+
+/// The errors of one kind in one column.
+#[derive(Debug, Default, PartialEq)]
+pub struct ErrorGroup {
+    /// How many errors there are.
+    pub count: usize,
+    /// The first few lines with the error.
+    pub sample_lines: Vec<u64>,
+}
+
+impl ErrorSummary {
+    /// Counts `error` in its group.
+    pub fn add(&mut self, error: &RowError) {
+        let column = error.error.column().unwrap_or("-").to_string();
+        let group = self.groups.entry((column, error.error.kind())).or_default();
+
+        group.count += 1;
+
+        if group.sample_lines.len() < SAMPLE_LINES {
+            group.sample_lines.push(error.line);
+        }
+    }
+
+    /// The groups of errors as `(column, kind, group)`, sorted by column.
+    pub fn groups(&self) -> impl Iterator<Item = (&str, &'static str, &ErrorGroup)> {
+        self.groups
+            .iter()
+            .map(|((column, kind), group)| (column.as_str(), *kind, group))
+    }
+
+    /// Logs a warning per group of errors.
+    pub fn log(&self) {
+        for (column, kind, group) in self.groups() {
+            let lines = group
+                .sample_lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            warn!(
+                "column {column}: {} {kind} error(s), e.g. on lines {lines}",
+                group.count
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{FieldError, FieldType};
+
+    fn invalid(line: u64, column: &str) -> RowError {
+        RowError {
+            line,
+            error: FieldError::InvalidValue {
+                column: column.into(),
+                value: "x".into(),
+                expected: FieldType::Int,
+            },
+        }
+    }
+
+    #[test]
+    fn group_errors_by_column_and_kind() {
+        let mut summary = ErrorSummary::default();
+
+        for line in 2..10 {
+            summary.add(&invalid(line, "count"));
+        }
+        summary.add(&invalid(4, "age"));
+        summary.add(&RowError {
+            line: 5,
+            error: FieldError::Missing {
+                column: "count".into(),
+            },
+        });
+
+        let groups = summary.groups().collect::<Vec<_>>();
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            groups[0],
+            (
+                "age",
+                "invalid_value",
+                &ErrorGroup {
+                    count: 1,
+                    sample_lines: vec![4]
+                }
+            )
+        );
+        assert_eq!(
+            groups[1],
+            (
+                "count",
+                "invalid_value",
+                &ErrorGroup {
+                    count: 8,
+                    sample_lines: vec![2, 3, 4, 5, 6]
+                }
+            )
+        );
+        assert_eq!(groups[2].1, "missing");
+    }
+}
@@ -0,0 +1,266 @@
+use time::{
+    format_description::{self, well_known::Rfc3339, OwnedFormatItem},
+    macros::format_description,
+    Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset,
+};
+
+use serde::Deserialize;
+
+/// How a timestamp may be written in a CSV cell, named in schemas as
+/// `rfc3339`, `epoch`, `epoch_seconds`, `epoch_millis` or a format
+/// description such as `[year]-[month]-[day]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TimestampFormat {
+    /// An ISO 8601 date and time with an offset, e.g.
+    /// `2024-01-31T12:00:00+08:00`.
+    Rfc3339,
+    /// Seconds since 1970-01-01 UTC.
+    EpochSeconds,
+    /// Milliseconds since 1970-01-01 UTC.
+    EpochMillis,
+    /// Seconds or milliseconds since 1970-01-01 UTC, told apart by magnitude:
+    /// numbers below 10^11 are seconds, as milliseconds they would be before
+    /// March 1973.
+    Epoch,
+    /// A [format description] of a date, or of a date and time with or
+    /// without an offset.
+    ///
+    /// [format description]: https://time-rs.github.io/book/api/format-description.html
+    Custom(OwnedFormatItem),
+}
+
+impl TryFrom<String> for TimestampFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            "epoch_seconds" => Ok(TimestampFormat::EpochSeconds),
+            "epoch_millis" => Ok(TimestampFormat::EpochMillis),
+            "epoch" => Ok(TimestampFormat::Epoch),
+            _ => format_description::parse_owned::<1>(&value)
+                .map(TimestampFormat::Custom)
+                .map_err(|err| format!("invalid timestamp format {value:?}: {err}")),
+        }
+    }
+}
+
+impl TimestampFormat {
+    /// The formats tried when a schema does not list any: RFC 3339, ISO 8601
+    /// local date and time such as `2023-11-14T22:13:20`, `YYYY-MM-DD` and
+    /// epoch seconds or millis.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            TimestampFormat::Rfc3339,
+            TimestampFormat::Custom(
+                format_description!(
+                    "[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]"
+                )
+                .into(),
+            ),
+            TimestampFormat::Custom(format_description!("[year]-[month]-[day]").into()),
+            TimestampFormat::Epoch,
+        ]
+    }
+
+    /// Parses `s`, reading dates and times without an offset as local to
+    /// `timezone`.
+    pub fn parse(&self, s: &str, timezone: UtcOffset) -> Option<OffsetDateTime> {
+        match self {
+            TimestampFormat::Rfc3339 => OffsetDateTime::parse(s, &Rfc3339).ok(),
+            TimestampFormat::EpochSeconds => {
+                OffsetDateTime::from_unix_timestamp(s.parse().ok()?).ok()
+            }
+            TimestampFormat::EpochMillis => {
+                let millis = s.parse::<i64>().ok()?;
+                OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000).ok()
+            }
+            TimestampFormat::Epoch => {
+                let epoch = s.parse::<i64>().ok()?;
+                let format = if epoch.unsigned_abs() < 100_000_000_000 {
+                    TimestampFormat::EpochSeconds
+                } else {
+                    TimestampFormat::EpochMillis
+                };
+                format.parse(s, timezone)
+            }
+            TimestampFormat::Custom(format) => OffsetDateTime::parse(s, format)
+                .or_else(|_| {
+                    PrimitiveDateTime::parse(s, format).map(|dt| dt.assume_offset(timezone))
+                })
+                .or_else(|_| {
+                    Date::parse(s, format)
+                        .map(|date| date.with_time(Time::MIDNIGHT).assume_offset(timezone))
+                })
+                .ok(),
+        }
+    }
+}
+
+/// The UTC offset of timestamps written without one, named in schemas as
+/// `UTC` or e.g. `+08:00`.
+///
+/// Only fixed offsets are supported, not IANA time zones such as
+/// `Asia/Singapore`, so daylight saving time is not accounted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Timezone(pub UtcOffset);
+
+impl Default for Timezone {
+    fn default() -> Self {
+        Self(UtcOffset::UTC)
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "UTC" || value == "Z" {
+            return Ok(Self::default());
+        }
+
+        UtcOffset::parse(
+            &value,
+            format_description!("[offset_hour sign:mandatory]:[offset_minute]"),
+        )
+        .map(Self)
+        .map_err(|err| format!("invalid timezone {value:?}, expected e.g. +08:00: {err}"))
+    }
+}
+
+/// How far ahead of this host's clock a timestamp may be before it counts
+/// as in the future, to allow for clock skew between systems.
+pub const MAX_CLOCK_SKEW_MILLIS: i64 = 5 * 60 * 1000;
+
+/// Which timestamps outside of 1970 to now are accepted, set per field in
+/// schemas with `allow_before_1970` and `allow_future`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampRange {
+    /// Accept timestamps before 1970, e.g. birth dates.
+    pub before_1970: bool,
+    /// Accept timestamps in the future, e.g. expiry dates.
+    pub future: bool,
+}
+
+impl TimestampRange {
+    /// Whether `millis` is in the range, `now` being the current time in
+    /// epoch millis.
+    pub fn contains(&self, millis: i64, now: i64) -> bool {
+        (self.before_1970 || millis >= 0)
+            && (self.future || millis <= now.saturating_add(MAX_CLOCK_SKEW_MILLIS))
+    }
+}
+
+/// Parses `s` with the first of `formats` that fits, into epoch millis.
+pub fn epoch_millis(s: &str, formats: &[TimestampFormat], timezone: UtcOffset) -> Option<i64> {
+    let timestamp = formats
+        .iter()
+        .find_map(|format| format.parse(s, timezone))?;
+
+    i64::try_from(timestamp.unix_timestamp_nanos() / 1_000_000).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::offset;
+
+    use super::*;
+
+    fn custom(format: &str) -> TimestampFormat {
+        TimestampFormat::try_from(format.to_string()).expect("Valid format")
+    }
+
+    #[test]
+    fn parse_default_formats() {
+        let formats = TimestampFormat::defaults();
+
+        assert_eq!(
+            epoch_millis("2023-11-14T22:13:20Z", &formats, UtcOffset::UTC),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(
+            epoch_millis("2023-11-14T22:13:20.5+01:00", &formats, UtcOffset::UTC),
+            Some(1_699_996_400_500)
+        );
+        assert_eq!(
+            epoch_millis("2023-11-14", &formats, UtcOffset::UTC),
+            Some(1_699_920_000_000)
+        );
+        assert_eq!(
+            epoch_millis("2023-11-15T06:13:20", &formats, offset!(+8)),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(
+            epoch_millis("2023-11-14T22:13:20.25", &formats, UtcOffset::UTC),
+            Some(1_700_000_000_250)
+        );
+    }
+
+    #[test]
+    fn tell_epoch_seconds_and_millis_apart() {
+        let formats = TimestampFormat::defaults();
+
+        assert_eq!(
+            epoch_millis("1700000000", &formats, UtcOffset::UTC),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(
+            epoch_millis("1700000000000", &formats, UtcOffset::UTC),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(
+            epoch_millis("99999999999", &formats, UtcOffset::UTC),
+            Some(99_999_999_999_000)
+        );
+        assert_eq!(
+            epoch_millis("100000000000", &formats, UtcOffset::UTC),
+            Some(100_000_000_000)
+        );
+    }
+
+    #[test]
+    fn parse_epoch_seconds() {
+        assert_eq!(
+            epoch_millis(
+                "1700000000",
+                &[TimestampFormat::EpochSeconds],
+                UtcOffset::UTC
+            ),
+            Some(1_700_000_000_000)
+        );
+    }
+
+    #[test]
+    fn read_local_times_in_timezone() {
+        let formats = [custom("[day]/[month]/[year] [hour]:[minute]")];
+
+        assert_eq!(
+            epoch_millis("15/11/2023 06:13", &formats, offset!(+8)),
+            Some(1_700_000_000_000 - 20_000)
+        );
+    }
+
+    #[test]
+    fn reject_impossible_dates() {
+        let formats = TimestampFormat::defaults();
+
+        assert_eq!(epoch_millis("2023-02-30", &formats, UtcOffset::UTC), None);
+        assert_eq!(epoch_millis("2023-13-01", &formats, UtcOffset::UTC), None);
+        assert_eq!(epoch_millis("yesterday", &formats, UtcOffset::UTC), None);
+    }
+
+    #[test]
+    fn parse_timezones() {
+        assert_eq!(
+            Timezone::try_from("UTC".to_string()),
+            Ok(Timezone::default())
+        );
+        assert_eq!(
+            Timezone::try_from("-03:30".to_string()),
+            Ok(Timezone(offset!(-3:30)))
+        );
+        assert!(Timezone::try_from("Asia/Singapore".to_string()).is_err());
+    }
+}
@@ -0,0 +1,592 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{auth::Auth, model::TypedEvent, rate_limit::RateLimiter, Endpoint, Event};
+
+/// How requests failing with 429, 5xx or a connection error are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// How many times a request is sent at most, including the first time.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled before every following one.
+    pub base_delay: Duration,
+    /// The longest delay before a retry. Backoff stops growing there, and a
+    /// request whose Retry-After asks for longer is not retried.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before retrying after `attempt` failed: half of it is the
+    /// exponential backoff, the other half is random jitter.
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+
+        backoff / 2 + backoff.mul_f64(jitter / 2.0)
+    }
+}
+
+/// Whether Feedzai accepted everything that was sent with `status`.
+///
+/// A 207 means a bulk request was only partly accepted.
+pub fn accepted(status: u16) -> bool {
+    (200..300).contains(&status) && status != 207
+}
+
+/// Posts validated events to a Feedzai instance.
+pub struct Uploader {
+    agent: ureq::Agent,
+    base_url: String,
+    retry: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    auth: Option<Auth>,
+    retries: AtomicU64,
+}
+
+impl Uploader {
+    /// Uploads to the Feedzai instance at `base_url`, without retries, rate
+    /// limit or credentials.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            agent: agent_builder().build(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::default(),
+            rate_limiter: None,
+            auth: None,
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    /// Authenticates every request with `auth`.
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Connects with `tls_config`, e.g. for mutual TLS or a private CA.
+    pub fn with_tls(mut self, tls_config: Arc<rustls::ClientConfig>) -> Self {
+        self.agent = agent_builder().tls_config(tls_config).build();
+        self
+    }
+
+    /// Retries transient failures according to `retry`.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sends no more than `max_rps` requests per second, across all threads
+    /// sharing this uploader.
+    pub fn with_max_rps(mut self, max_rps: f64) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(max_rps));
+        self
+    }
+
+    /// How long uploads have waited for the rate limit in total.
+    pub fn rate_limit_wait(&self) -> Duration {
+        self.rate_limiter
+            .as_ref()
+            .map_or(Duration::ZERO, RateLimiter::waited)
+    }
+
+    /// How many requests have been retried in total.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Sends a single event and returns the HTTP status Feedzai answered with.
+    ///
+    /// Non-2xx responses are not errors here, only transport failures are.
+    pub fn upload(&self, endpoint: Endpoint, event: &Event) -> eyre::Result<u16> {
+        self.post(endpoint.path(), &event_id(endpoint, event), event)
+    }
+
+    /// Sends a typed event to its endpoint.
+    pub fn upload_typed<T: TypedEvent>(&self, event: &T) -> eyre::Result<u16> {
+        self.upload(T::ENDPOINT, &event.to_event())
+    }
+
+    /// Sends `events` as one array to the bulk path of `endpoint` and returns
+    /// the status of every event.
+    ///
+    /// Batches Feedzai answers with 207 or 4xx, and endpoints without bulk
+    /// ingestion, fall back to uploading the events one at a time, to find
+    /// out which events were rejected. Any other status, such as a 429 or
+    /// 5xx left after retrying, is the status of every event of the batch.
+    pub fn upload_batch(&self, endpoint: Endpoint, events: &[&Event]) -> eyre::Result<Vec<u16>> {
+        if let (Some(path), true) = (endpoint.bulk_path(), events.len() > 1) {
+            let id = format!(
+                "batch {}..{}",
+                event_id(endpoint, events[0]),
+                event_id(endpoint, events[events.len() - 1])
+            );
+            let status = self.post(path, &id, events)?;
+
+            if status != 207 && !(400..500).contains(&status) {
+                return Ok(vec![status; events.len()]);
+            }
+
+            warn!(
+                "batch of {} events answered with {status}, uploading them one at a time",
+                events.len()
+            );
+        }
+
+        events
+            .iter()
+            .map(|event| self.upload(endpoint, event))
+            .collect()
+    }
+
+    /// Posts `body`, retrying transient failures according to the retry
+    /// policy. `id` identifies what is posted in the logs.
+    ///
+    /// A Retry-After in seconds replaces the backoff. If it is longer than
+    /// the maximum delay of the policy, the status is returned without
+    /// retrying. The HTTP-date form of Retry-After is ignored.
+    fn post(&self, path: &str, id: &str, body: impl Serialize) -> eyre::Result<u16> {
+        let url = format!("{}{path}", self.base_url);
+
+        for attempt in 1.. {
+            let last_attempt = attempt >= self.retry.max_attempts;
+
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire();
+            }
+
+            let mut request = self.agent.post(&url);
+
+            if let Some(auth) = &self.auth {
+                let (name, value) = auth.header();
+                request = request.set(name, &value);
+            }
+
+            let (reason, retry_after) = match request.send_json(&body) {
+                Ok(response) => return Ok(response.status()),
+                Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+                    if last_attempt {
+                        return Ok(status);
+                    }
+
+                    let retry_after = response
+                        .header("Retry-After")
+                        .and_then(|value| value.trim().parse().ok())
+                        .map(Duration::from_secs);
+
+                    if let Some(retry_after) = retry_after.filter(|&d| d > self.retry.max_delay) {
+                        warn!(
+                            "{id}: attempt {attempt} failed with {status}, not retrying as \
+                             Retry-After {retry_after:?} exceeds the maximum delay {:?}",
+                            self.retry.max_delay
+                        );
+                        return Ok(status);
+                    }
+
+                    (status.to_string(), retry_after)
+                }
+                Err(ureq::Error::Status(status, _)) => return Ok(status),
+                Err(err) => {
+                    if last_attempt {
+                        return Err(err.into());
+                    }
+
+                    (err.to_string(), None)
+                }
+            };
+
+            let delay = retry_after.unwrap_or_else(|| self.retry.delay(attempt));
+
+            warn!("{id}: attempt {attempt} failed with {reason}, retrying in {delay:?}");
+            self.retries.fetch_add(1, Ordering::Relaxed);
+
+            std::thread::sleep(delay);
+        }
+
+        unreachable!("retries until the last attempt")
+    }
+}
+
+fn agent_builder() -> ureq::AgentBuilder {
+    ureq::AgentBuilder::new().timeout(Duration::from_secs(30))
+}
+
+/// The value of the id field of `event`, for logging.
+fn event_id(endpoint: Endpoint, event: &Event) -> String {
+    match event.get(endpoint.id_field()) {
+        Some(Event::String(id)) => id.clone(),
+        Some(id) => id.to_string(),
+        None => "event without id".to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::model::Card;
+
+    /// A request received by [`stub_server`].
+    pub(crate) struct StubRequest {
+        pub line: String,
+        pub headers: Vec<String>,
+        pub body: String,
+    }
+
+    /// Answers one connection per status in `statuses` and returns the
+    /// requests it received.
+    pub(crate) fn stub_server(statuses: Vec<u16>) -> (String, JoinHandle<Vec<StubRequest>>) {
+        stub_server_with_headers(statuses.into_iter().map(|status| (status, "")).collect())
+    }
+
+    /// Like [`stub_server`], also sending the given header lines with each
+    /// status.
+    pub(crate) fn stub_server_with_headers(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, JoinHandle<Vec<StubRequest>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Bound listener");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|(status, headers)| {
+                    let (stream, _) = listener.accept().expect("Accepted connection");
+                    let mut reader = BufReader::new(stream);
+
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+
+                    let mut request_headers = Vec::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                        request_headers.push(line.trim().to_string());
+                    }
+
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {status} Stub\r\n{headers}Content-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+
+                    StubRequest {
+                        line: request_line.trim().to_string(),
+                        headers: request_headers,
+                        body: String::from_utf8(body).unwrap(),
+                    }
+                })
+                .collect()
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn upload_posts_event_to_endpoint_path() {
+        let (url, server) = stub_server(vec![200]);
+        let uploader = Uploader::new(&url);
+
+        let status = uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        let requests = server.join().unwrap();
+
+        assert_eq!(status, 200);
+        assert_eq!(
+            requests[0].line,
+            format!("POST {} HTTP/1.1", Endpoint::ReferenceDataAccount.path())
+        );
+        assert_eq!(
+            serde_json::from_str::<Event>(&requests[0].body).unwrap(),
+            json!({"account_id": "a1"})
+        );
+    }
+
+    #[test]
+    fn upload_typed_event_to_its_endpoint() {
+        let (url, server) = stub_server(vec![200]);
+        let uploader = Uploader::new(&url);
+
+        let card = Card {
+            card_id: "c1".into(),
+            card_is_virtual: Some(true),
+            ..Default::default()
+        };
+
+        let status = uploader.upload_typed(&card).expect("Uploaded event");
+
+        let requests = server.join().unwrap();
+
+        assert_eq!(status, 200);
+        assert_eq!(
+            requests[0].line,
+            format!("POST {} HTTP/1.1", Endpoint::ReferenceDataCard.path())
+        );
+        assert_eq!(
+            serde_json::from_str::<Event>(&requests[0].body).unwrap(),
+            json!({"card_id": "c1", "card_is_virtual": true})
+        );
+    }
+
+    #[test]
+    fn upload_batch_to_bulk_path() {
+        let (url, server) = stub_server(vec![200]);
+        let uploader = Uploader::new(&url);
+        let events = [json!({"account_id": "a1"}), json!({"account_id": "a2"})];
+
+        let statuses = uploader
+            .upload_batch(Endpoint::ReferenceDataAccount, &[&events[0], &events[1]])
+            .expect("Uploaded batch");
+
+        let requests = server.join().unwrap();
+
+        assert_eq!(statuses, vec![200, 200]);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].line,
+            format!(
+                "POST {} HTTP/1.1",
+                Endpoint::ReferenceDataAccount.bulk_path().unwrap()
+            )
+        );
+        assert_eq!(
+            serde_json::from_str::<Event>(&requests[0].body).unwrap(),
+            json!([{"account_id": "a1"}, {"account_id": "a2"}])
+        );
+    }
+
+    #[test]
+    fn upload_partly_rejected_batch_one_at_a_time() {
+        let (url, server) = stub_server(vec![207, 200, 400]);
+        let uploader = Uploader::new(&url);
+        let events = [json!({"account_id": "a1"}), json!({"account_id": ""})];
+
+        let statuses = uploader
+            .upload_batch(Endpoint::ReferenceDataAccount, &[&events[0], &events[1]])
+            .expect("Uploaded batch");
+
+        let requests = server.join().unwrap();
+
+        assert_eq!(statuses, vec![200, 400]);
+        assert_eq!(
+            requests
+                .iter()
+                .map(|request| request.line.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "POST /api/v1/reference-data/accounts/bulk HTTP/1.1",
+                "POST /api/v1/reference-data/accounts HTTP/1.1",
+                "POST /api/v1/reference-data/accounts HTTP/1.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_whole_batch_on_server_error() {
+        let (url, server) = stub_server(vec![503]);
+        let uploader = Uploader::new(&url);
+        let events = [json!({"account_id": "a1"}), json!({"account_id": "a2"})];
+
+        let statuses = uploader
+            .upload_batch(Endpoint::ReferenceDataAccount, &[&events[0], &events[1]])
+            .expect("Uploaded batch");
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(statuses, vec![503, 503]);
+    }
+
+    #[test]
+    fn upload_batch_without_bulk_endpoint_one_at_a_time() {
+        let (url, server) = stub_server(vec![200, 200]);
+        let uploader = Uploader::new(&url);
+        let events = [json!({"id": "t1"}), json!({"id": "t2"})];
+
+        let statuses = uploader
+            .upload_batch(Endpoint::TransferInitiation, &[&events[0], &events[1]])
+            .expect("Uploaded batch");
+
+        assert_eq!(server.join().unwrap().len(), 2);
+        assert_eq!(statuses, vec![200, 200]);
+    }
+
+    #[test]
+    fn upload_reports_rejected_status() {
+        let (url, server) = stub_server(vec![422]);
+        let uploader = Uploader::new(&url);
+
+        let status = uploader
+            .upload(Endpoint::CardAuthorization, &json!({}))
+            .expect("Uploaded event");
+
+        server.join().unwrap();
+
+        assert_eq!(status, 422);
+    }
+
+    fn retrying(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn retry_transient_failures() {
+        let (url, server) =
+            stub_server_with_headers(vec![(503, ""), (429, "Retry-After: 0\r\n"), (200, "")]);
+        let uploader = Uploader::new(&url).with_retry(retrying(3));
+
+        let status = uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        assert_eq!(server.join().unwrap().len(), 3);
+        assert_eq!(status, 200);
+        assert_eq!(uploader.retries(), 2);
+    }
+
+    #[test]
+    fn give_up_after_max_attempts() {
+        let (url, server) = stub_server(vec![503, 503]);
+        let uploader = Uploader::new(&url).with_retry(retrying(2));
+
+        let status = uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        assert_eq!(server.join().unwrap().len(), 2);
+        assert_eq!(status, 503);
+        assert_eq!(uploader.retries(), 1);
+    }
+
+    #[test]
+    fn never_retry_validation_errors() {
+        let (url, server) = stub_server(vec![400]);
+        let uploader = Uploader::new(&url).with_retry(retrying(3));
+
+        let status = uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn retry_connection_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let uploader = Uploader::new(&url).with_retry(retrying(2));
+
+        assert!(uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .is_err());
+        assert_eq!(uploader.retries(), 1);
+    }
+
+    #[test]
+    fn give_up_when_retry_after_exceeds_max_delay() {
+        let (url, server) = stub_server_with_headers(vec![(429, "Retry-After: 3600\r\n")]);
+        let uploader = Uploader::new(&url).with_retry(retrying(3));
+
+        let status = uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(status, 429);
+        assert_eq!(uploader.retries(), 0);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let retry = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+
+        assert!((50..=100).contains(&retry.delay(1).as_millis()));
+        assert!((200..=400).contains(&retry.delay(3).as_millis()));
+        assert!((250..=500).contains(&retry.delay(5).as_millis()));
+    }
+
+    #[test]
+    fn rate_limit_uploads() {
+        let (url, server) = stub_server(vec![200, 200, 200]);
+        let uploader = Uploader::new(&url).with_max_rps(50.0);
+
+        for _ in 0..3 {
+            uploader
+                .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+                .expect("Uploaded event");
+        }
+
+        server.join().unwrap();
+
+        assert!(uploader.rate_limit_wait() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn authenticate_uploads() {
+        let (url, server) = stub_server(vec![200]);
+        let uploader = Uploader::new(&url).with_auth(Auth::ApiKey("s3cr3t".parse().unwrap()));
+
+        uploader
+            .upload(Endpoint::ReferenceDataAccount, &json!({"account_id": "a1"}))
+            .expect("Uploaded event");
+
+        let requests = server.join().unwrap();
+
+        assert!(requests[0]
+            .headers
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case("x-api-key: s3cr3t")));
+    }
+}
@@ -0,0 +1,926 @@
+use serde_json::Map;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::{
+    currency,
+    error::{FieldError, FieldType},
+    path,
+    schema::{ArrayRule, Booleans, Currency, ElementType, EmptyPolicy, EnumRule},
+    timestamp::{self, TimestampFormat, TimestampRange},
+    Event,
+};
+
+/// Turns a CSV event into the event Feedzai expects, e.g. a [`Schema`].
+///
+/// [`Schema`]: crate::schema::Schema
+pub trait Validator {
+    /// Validates `event`, returning every field error found in it.
+    fn validate(&self, event: Event) -> Result<Event, Vec<FieldError>>;
+}
+
+/// Field conversions and checks of an event, the building blocks of
+/// validators.
+///
+/// The conversions take the CSV text of fields and skip absent ones.
+pub trait EventValidation {
+    /// Validates this event with `validator`.
+    fn validate(self, validator: &dyn Validator) -> Result<Event, Vec<FieldError>>;
+
+    /// Removes `keys`.
+    fn drop_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as JSON arrays.
+    fn array_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as arrays written as in `rule`, and their elements as
+    /// the type of its elements.
+    fn array_fields_with(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        rule: &ArrayRule,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as integers.
+    fn int_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as floating point numbers.
+    fn float_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Checks that `keys` are strings.
+    fn str_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as `true` or `false`.
+    fn bool_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError>;
+
+    /// Parses `keys` as booleans spelled as in `booleans`.
+    fn bool_fields_with(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        booleans: &Booleans,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Converts `keys` to epoch millis with the first of `formats` that
+    /// fits, reading times without an offset as local to `timezone`.
+    /// Timestamps outside of `range` are rejected.
+    fn timestamp_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        formats: &[TimestampFormat],
+        timezone: UtcOffset,
+        range: TimestampRange,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Converts the decimal amounts of `keys` exactly to minor units of their
+    /// ISO 4217 `currency`, e.g. `"10.10"` EUR to 1010.
+    fn amount_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        currency: &Currency,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Applies `policy` to the fields of `keys` that are empty strings.
+    fn empty_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        policy: &EmptyPolicy,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Checks that `key` is present.
+    fn require(&self, key: &str) -> Result<(), FieldError>;
+
+    /// Replaces the values of `keys` with the value of `rule` they match,
+    /// either as is, regardless of case or as an alias.
+    fn enum_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        rule: &EnumRule,
+    ) -> Result<&mut Self, FieldError>;
+
+    /// Converts `key`, if present, from its CSV text to `expected`.
+    fn convert(&mut self, key: &str, expected: FieldType) -> Result<&mut Self, FieldError>;
+}
+
+impl EventValidation for Event {
+    fn validate(self, validator: &dyn Validator) -> Result<Event, Vec<FieldError>> {
+        validator.validate(self)
+    }
+
+    fn drop_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            path::remove(obj, key);
+        }
+
+        Ok(self)
+    }
+
+    fn array_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        for key in keys.iter().map(AsRef::as_ref) {
+            self.convert(key, FieldType::Array)?;
+        }
+
+        Ok(self)
+    }
+
+    fn array_fields_with(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        rule: &ArrayRule,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            if let Some(value) = present_mut(obj, key) {
+                let elements = value
+                    .as_str()
+                    .and_then(|s| split_array(s, rule.delimiter.as_deref()))
+                    .ok_or_else(|| FieldError::InvalidValue {
+                        column: key.to_string(),
+                        value: raw_value(value),
+                        expected: FieldType::Array,
+                    })?;
+
+                *value = elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, element)| {
+                        parse_element(&format!("{key}[{index}]"), element, rule)
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Event::Array)?;
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn int_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        for key in keys.iter().map(AsRef::as_ref) {
+            self.convert(key, FieldType::Int)?;
+        }
+
+        Ok(self)
+    }
+
+    fn float_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        for key in keys.iter().map(AsRef::as_ref) {
+            self.convert(key, FieldType::Float)?;
+        }
+
+        Ok(self)
+    }
+
+    fn str_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        for key in keys.iter().map(AsRef::as_ref) {
+            self.convert(key, FieldType::Str)?;
+        }
+
+        Ok(self)
+    }
+
+    fn bool_fields(&mut self, keys: &[impl AsRef<str>]) -> Result<&mut Self, FieldError> {
+        for key in keys.iter().map(AsRef::as_ref) {
+            self.convert(key, FieldType::Bool)?;
+        }
+
+        Ok(self)
+    }
+
+    fn bool_fields_with(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        booleans: &Booleans,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            if let Some(value) = present_mut(obj, key) {
+                *value = value
+                    .as_str()
+                    .and_then(|s| booleans.parse(s))
+                    .ok_or_else(|| FieldError::NotAllowed {
+                        column: key.to_string(),
+                        value: raw_value(value),
+                        allowed: booleans.spellings(),
+                    })?
+                    .into();
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn timestamp_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        formats: &[TimestampFormat],
+        timezone: UtcOffset,
+        range: TimestampRange,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+        let now = OffsetDateTime::now_utc().unix_timestamp() * 1000;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            let Some(value) = present_mut(obj, key) else {
+                continue;
+            };
+
+            let millis = value
+                .as_str()
+                .and_then(|s| timestamp::epoch_millis(s, formats, timezone))
+                .ok_or_else(|| FieldError::InvalidValue {
+                    column: key.to_string(),
+                    value: raw_value(value),
+                    expected: FieldType::Timestamp,
+                })?;
+
+            if !range.contains(millis, now) {
+                return Err(FieldError::OutOfRange {
+                    column: key.to_string(),
+                    value: raw_value(value),
+                });
+            }
+
+            *value = millis.into();
+        }
+
+        Ok(self)
+    }
+
+    fn amount_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        currency: &Currency,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            let Some(value) = present(obj, key) else {
+                continue;
+            };
+
+            let amount = value
+                .as_str()
+                .filter(|s| currency::is_decimal(s))
+                .ok_or_else(|| FieldError::InvalidValue {
+                    column: key.to_string(),
+                    value: raw_value(value),
+                    expected: FieldType::Amount,
+                })?;
+
+            let (code, currency_column) = match currency {
+                Currency::Column(column) => {
+                    let code = present(obj, column).ok_or_else(|| FieldError::Missing {
+                        column: column.clone(),
+                    })?;
+
+                    (raw_value(code), column.as_str())
+                }
+                Currency::Fixed { currency } => (currency.clone(), key),
+            };
+
+            let exponent =
+                currency::exponent(&code).ok_or_else(|| FieldError::UnknownCurrency {
+                    column: currency_column.to_string(),
+                    value: code.clone(),
+                })?;
+
+            let units =
+                currency::minor_units(amount, exponent).ok_or_else(|| FieldError::TooPrecise {
+                    column: key.to_string(),
+                    value: amount.to_string(),
+                    currency: code,
+                })?;
+
+            if let Some(value) = path::get_mut(obj, key) {
+                *value = units.into();
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn empty_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        policy: &EmptyPolicy,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            let Some(value) = path::get_mut(obj, key).filter(|value| *value == "") else {
+                continue;
+            };
+
+            match policy {
+                EmptyPolicy::Keep => {}
+                EmptyPolicy::Null => *value = Event::Null,
+                EmptyPolicy::Default(default) => *value = default.as_str().into(),
+                EmptyPolicy::Reject => {
+                    return Err(FieldError::Empty {
+                        column: key.to_string(),
+                    })
+                }
+                EmptyPolicy::Omit => {
+                    path::remove(obj, key);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn require(&self, key: &str) -> Result<(), FieldError> {
+        let obj = self.as_object().ok_or(FieldError::NotAnObject)?;
+
+        if present(obj, key).is_none() {
+            return Err(FieldError::Missing {
+                column: key.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn enum_fields(
+        &mut self,
+        keys: &[impl AsRef<str>],
+        rule: &EnumRule,
+    ) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        for key in keys.iter().map(AsRef::as_ref) {
+            if let Some(value) = present_mut(obj, key) {
+                let raw = raw_value(value);
+
+                *value = rule
+                    .resolve(&raw)
+                    .ok_or_else(|| FieldError::NotAllowed {
+                        column: key.to_string(),
+                        value: raw.clone(),
+                        allowed: rule.spellings(),
+                    })?
+                    .into();
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Leaves the event untouched if the value does not parse.
+    fn convert(&mut self, key: &str, expected: FieldType) -> Result<&mut Self, FieldError> {
+        let obj = self.as_object_mut().ok_or(FieldError::NotAnObject)?;
+
+        if let Some(value) = present_mut(obj, key) {
+            *value = value
+                .as_str()
+                .and_then(|s| expected.parse(s))
+                .ok_or_else(|| FieldError::InvalidValue {
+                    column: key.to_string(),
+                    value: raw_value(value),
+                    expected,
+                })?;
+        }
+
+        Ok(self)
+    }
+}
+
+impl FieldType {
+    fn parse(self, s: &str) -> Option<serde_json::Value> {
+        match self {
+            FieldType::Array => serde_json::from_str(s).ok().map(serde_json::Value::Array),
+            FieldType::Int => s.parse::<i64>().ok().map(Into::into),
+            FieldType::Float => s.parse::<f64>().ok().map(Into::into),
+            FieldType::Str => Some(s.into()),
+            FieldType::Bool => s.parse::<bool>().ok().map(Into::into),
+            // Amounts need the currency of their row, see `amount_fields`.
+            FieldType::Amount => None,
+            FieldType::Object => serde_json::from_str(s)
+                .ok()
+                .filter(serde_json::Value::is_object),
+            FieldType::Timestamp => {
+                timestamp::epoch_millis(s, &TimestampFormat::defaults(), UtcOffset::UTC)
+                    .map(Into::into)
+            }
+        }
+    }
+}
+
+/// The value at `key` if it is to be validated, i.e. present and not null.
+fn present<'a>(obj: &'a Map<String, Event>, key: &str) -> Option<&'a Event> {
+    path::get(obj, key).filter(|value| !value.is_null())
+}
+
+/// The value at `key` if it is to be converted, see [`present`].
+fn present_mut<'a>(obj: &'a mut Map<String, Event>, key: &str) -> Option<&'a mut Event> {
+    path::get_mut(obj, key).filter(|value| !value.is_null())
+}
+
+/// The elements of a JSON array, or of a list of strings separated by
+/// `delimiter`.
+fn split_array(s: &str, delimiter: Option<&str>) -> Option<Vec<Event>> {
+    if let Ok(Event::Array(elements)) = serde_json::from_str(s) {
+        return Some(elements);
+    }
+
+    match delimiter? {
+        _ if s.is_empty() => Some(Vec::new()),
+        delimiter => Some(s.split(delimiter).map(Into::into).collect()),
+    }
+}
+
+/// Converts an array element found at `column` to the element type of
+/// `rule`.
+///
+/// Elements are validated from their text, like CSV cells: the scalar
+/// fields of objects are turned back into text before `rule.schema` is
+/// applied to them.
+fn parse_element(column: &str, element: Event, rule: &ArrayRule) -> Result<Event, FieldError> {
+    let invalid = |element: &Event, expected| FieldError::InvalidValue {
+        column: column.to_string(),
+        value: raw_value(element),
+        expected,
+    };
+
+    let parse = |expected: FieldType| {
+        expected
+            .parse(&raw_value(&element))
+            .ok_or_else(|| invalid(&element, expected))
+    };
+
+    match rule.elements {
+        ElementType::Any => Ok(element),
+        ElementType::Str => parse(FieldType::Str),
+        ElementType::Int => parse(FieldType::Int),
+        ElementType::Float => parse(FieldType::Float),
+        ElementType::Bool => rule
+            .schema
+            .booleans
+            .parse(&raw_value(&element))
+            .map(Into::into)
+            .ok_or_else(|| FieldError::NotAllowed {
+                column: column.to_string(),
+                value: raw_value(&element),
+                allowed: rule.schema.booleans.spellings(),
+            }),
+        ElementType::Object => {
+            let Event::Object(mut fields) = element else {
+                return Err(invalid(&element, FieldType::Object));
+            };
+
+            // Fields the element schema converts are read as if they were
+            // CSV cells, the others keep their JSON type.
+            for column in rule.schema.columns() {
+                if let Some(value) = present_mut(&mut fields, column) {
+                    *value = raw_value(value).into();
+                }
+            }
+
+            rule.schema
+                .validate(Event::Object(fields))
+                .map_err(|mut errors| errors.swap_remove(0).nested_in(column))
+        }
+    }
+}
+
+/// The CSV text of `value`, or its JSON form if it was already converted.
+fn raw_value(value: &Event) -> String {
+    match value.as_str() {
+        Some(s) => s.to_string(),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{error::RowError, schema::Schema};
+
+    #[test]
+    fn validate_array_fields() {
+        let mut input = json!({
+            "field": r#"["one","two"]"#,
+            "expected": ["one","two"]
+        });
+
+        let event = input.array_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(event["field"], event["expected"]);
+    }
+
+    #[test]
+    fn validate_delimited_array_fields() {
+        let rule = ArrayRule {
+            elements: ElementType::Int,
+            delimiter: Some("|".into()),
+            ..Default::default()
+        };
+
+        for (cell, expected) in [
+            ("1|2|3", json!([1, 2, 3])),
+            ("[1,2]", json!([1, 2])),
+            ("", json!([])),
+        ] {
+            let mut input = json!({"field": cell});
+
+            let event = input
+                .array_fields_with(&["field"], &rule)
+                .expect("Validated event");
+
+            assert_eq!(event["field"], expected);
+        }
+
+        assert_eq!(
+            json!({"field": "1|x"})
+                .array_fields_with(&["field"], &rule)
+                .unwrap_err(),
+            FieldError::InvalidValue {
+                column: "field[1]".into(),
+                value: "x".into(),
+                expected: FieldType::Int,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_object_array_elements() {
+        let rule = ArrayRule {
+            elements: ElementType::Object,
+            schema: Schema {
+                required: vec!["limit_type".into()],
+                float: vec!["limit_amount".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut input = json!({
+            "field": r#"[{"limit_type": "daily", "limit_amount": 500}]"#
+        });
+
+        let event = input
+            .array_fields_with(&["field"], &rule)
+            .expect("Validated event");
+
+        assert_eq!(
+            event["field"],
+            json!([{"limit_type": "daily", "limit_amount": 500.0}])
+        );
+
+        let limits = json!([{
+            "limit_type": "daily",
+            "limit_amount": 5,
+            "shared": true,
+            "window": {"days": 1},
+        }]);
+        let mut input = json!({"field": limits.to_string()});
+
+        let event = input
+            .array_fields_with(&["field"], &rule)
+            .expect("Validated event");
+
+        assert_eq!(
+            event["field"],
+            json!([{
+                "limit_type": "daily",
+                "limit_amount": 5.0,
+                "shared": true,
+                "window": {"days": 1},
+            }])
+        );
+
+        for (cell, column) in [
+            (
+                r#"[{"limit_type": "daily"}, {"limit_amount": 5}]"#,
+                "field[1].limit_type",
+            ),
+            (
+                r#"[{"limit_type": "daily", "limit_amount": "x"}]"#,
+                "field[0].limit_amount",
+            ),
+            (r#"["daily"]"#, "field[0]"),
+        ] {
+            let error = json!({"field": cell})
+                .array_fields_with(&["field"], &rule)
+                .unwrap_err();
+
+            assert_eq!(error.column(), Some(column));
+        }
+    }
+
+    #[test]
+    fn validate_int_fields() {
+        let mut input = json!({
+            "field": "123",
+            "expected": 123
+        });
+
+        let event = input.int_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(event["field"], event["expected"]);
+    }
+
+    #[test]
+    fn validate_float_fields() {
+        let mut input = json!({
+            "field": "10.5",
+            "expected": 10.5
+        });
+
+        let event = input.float_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(event["field"], event["expected"]);
+    }
+
+    #[test]
+    fn validate_bool_fields() {
+        let mut input = json!({
+            "field": "true",
+            "expected": true
+        });
+
+        let event = input.bool_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(event["field"], event["expected"]);
+    }
+
+    #[test]
+    fn validate_timestamp_fields() {
+        let mut input = json!({
+            "field": "2023-11-14",
+            "expected": 1_699_920_000_000_i64
+        });
+
+        let event = input
+            .timestamp_fields(
+                &["field"],
+                &TimestampFormat::defaults(),
+                UtcOffset::UTC,
+                TimestampRange::default(),
+            )
+            .expect("Validated event");
+
+        assert_eq!(event["field"], event["expected"]);
+    }
+
+    #[test]
+    fn reject_timestamps_out_of_range() {
+        for timestamp in ["1969-12-31", "2999-01-01"] {
+            let mut input = json!({"field": timestamp});
+
+            assert_eq!(
+                input
+                    .timestamp_fields(
+                        &["field"],
+                        &TimestampFormat::defaults(),
+                        UtcOffset::UTC,
+                        TimestampRange::default(),
+                    )
+                    .unwrap_err(),
+                FieldError::OutOfRange {
+                    column: "field".into(),
+                    value: timestamp.into(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn allow_timestamps_out_of_range() {
+        let mut input = json!({
+            "birth": "1965-05-01",
+            "expiry": "2999-01-01",
+            "skewed": (OffsetDateTime::now_utc().unix_timestamp() + 60).to_string(),
+        });
+
+        let range = |before_1970, future| TimestampRange {
+            before_1970,
+            future,
+        };
+        let event = input
+            .timestamp_fields(
+                &["birth"],
+                &TimestampFormat::defaults(),
+                UtcOffset::UTC,
+                range(true, false),
+            )
+            .and_then(|event| {
+                event.timestamp_fields(
+                    &["expiry"],
+                    &TimestampFormat::defaults(),
+                    UtcOffset::UTC,
+                    range(false, true),
+                )
+            })
+            .and_then(|event| {
+                event.timestamp_fields(
+                    &["skewed"],
+                    &TimestampFormat::defaults(),
+                    UtcOffset::UTC,
+                    TimestampRange::default(),
+                )
+            })
+            .expect("Validated event");
+
+        assert_eq!(event["birth"], json!(-147_398_400_000_i64));
+        assert_eq!(event["expiry"], json!(32_472_144_000_000_i64));
+        assert!(event["skewed"].is_i64());
+    }
+
+    fn column(name: &str) -> Currency {
+        Currency::Column(name.into())
+    }
+
+    #[test]
+    fn validate_amount_fields() {
+        let mut input = json!({
+            "eur": "10.10",
+            "jpy": "1500",
+            "bhd": "1.234",
+            "usd": "10.99",
+            "eur_currency": "EUR",
+            "jpy_currency": "JPY",
+            "bhd_currency": "BHD",
+        });
+
+        input
+            .amount_fields(&["eur"], &column("eur_currency"))
+            .and_then(|event| event.amount_fields(&["jpy"], &column("jpy_currency")))
+            .and_then(|event| event.amount_fields(&["bhd"], &column("bhd_currency")))
+            .and_then(|event| {
+                event.amount_fields(
+                    &["usd"],
+                    &Currency::Fixed {
+                        currency: "USD".into(),
+                    },
+                )
+            })
+            .expect("Validated event");
+
+        assert_eq!(input["eur"], 1010);
+        assert_eq!(input["jpy"], 1500);
+        assert_eq!(input["bhd"], 1234);
+        assert_eq!(input["usd"], 1099);
+    }
+
+    #[test]
+    fn reject_invalid_amounts() {
+        let cases = [
+            (
+                json!({"amount": "NaN", "currency": "EUR"}),
+                FieldError::InvalidValue {
+                    column: "amount".into(),
+                    value: "NaN".into(),
+                    expected: FieldType::Amount,
+                },
+            ),
+            (
+                json!({"amount": "10.5", "currency": "JPY"}),
+                FieldError::TooPrecise {
+                    column: "amount".into(),
+                    value: "10.5".into(),
+                    currency: "JPY".into(),
+                },
+            ),
+            (
+                json!({"amount": "10", "currency": "XYZ"}),
+                FieldError::UnknownCurrency {
+                    column: "currency".into(),
+                    value: "XYZ".into(),
+                },
+            ),
+            (
+                json!({"amount": "10"}),
+                FieldError::Missing {
+                    column: "currency".into(),
+                },
+            ),
+        ];
+
+        for (mut input, error) in cases {
+            assert_eq!(
+                input
+                    .amount_fields(&["amount"], &column("currency"))
+                    .unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn validate_enum_fields() {
+        let rule = EnumRule {
+            values: vec!["active".into(), "closed".into()],
+            aliases: [("A".to_string(), "active".to_string())].into(),
+            case_insensitive: true,
+        };
+
+        for (value, expected) in [("active", "active"), ("ACTIVE", "active"), ("a", "active")] {
+            let mut input = json!({"field": value});
+
+            let event = input
+                .enum_fields(&["field"], &rule)
+                .expect("Validated event");
+
+            assert_eq!(event["field"], expected);
+        }
+    }
+
+    #[test]
+    fn reject_values_outside_enum() {
+        let rule = EnumRule::from(vec!["active".to_string(), "closed".to_string()]);
+        let mut input = json!({"field": "ACTIVE"});
+
+        assert_eq!(
+            input.enum_fields(&["field"], &rule).unwrap_err(),
+            FieldError::NotAllowed {
+                column: "field".into(),
+                value: "ACTIVE".into(),
+                allowed: vec!["active".into(), "closed".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn apply_empty_cell_policies() {
+        let cases = [
+            (EmptyPolicy::Keep, json!({"field": "", "other": ""})),
+            (EmptyPolicy::Omit, json!({"other": ""})),
+            (EmptyPolicy::Null, json!({"field": null, "other": ""})),
+            (
+                EmptyPolicy::Default("0".into()),
+                json!({"field": "0", "other": ""}),
+            ),
+        ];
+
+        for (policy, expected) in cases {
+            let mut input = json!({"field": "", "other": ""});
+
+            let event = input
+                .empty_fields(&["field"], &policy)
+                .expect("Validated event");
+
+            assert_eq!(*event, expected);
+        }
+
+        assert_eq!(
+            json!({"field": ""})
+                .empty_fields(&["field"], &EmptyPolicy::Reject)
+                .unwrap_err(),
+            FieldError::Empty {
+                column: "field".into()
+            }
+        );
+    }
+
+    #[test]
+    fn leave_null_fields_unconverted() {
+        let mut input = json!({"field": null});
+
+        let event = input.int_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(*event, json!({"field": null}));
+    }
+
+    #[test]
+    fn convert_already_converted_field() {
+        let mut input = json!({"field": "123"});
+
+        let event = input.int_fields(&["field"]).expect("Validated event");
+
+        assert_eq!(
+            event.int_fields(&["field"]).unwrap_err(),
+            FieldError::InvalidValue {
+                column: "field".into(),
+                value: "123".into(),
+                expected: FieldType::Int,
+            }
+        );
+    }
+
+    #[test]
+    fn convert_invalid_value() {
+        let mut input = json!({"field": "12x"});
+
+        let error = input.int_fields(&["field"]).unwrap_err();
+
+        assert_eq!(
+            RowError { line: 7, error }.to_string(),
+            r#"line 7: column field is "12x", expected int"#
+        );
+    }
+
+    #[test]
+    fn convert_non_object_event() {
+        let mut input = json!(["field"]);
+
+        assert_eq!(
+            input.str_fields(&["field"]).unwrap_err(),
+            FieldError::NotAnObject
+        );
+    }
+}